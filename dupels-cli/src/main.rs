@@ -1,10 +1,13 @@
 use dupels_lib::{Cli, run_cli};
 use clap::Parser;
+use std::io::{self, Write};
 
 fn main() {
     let args = Cli::parse();
-    let output = run_cli(&args);
-    if !output.is_empty() {
-        println!("{}", output);
+    let mut output = run_cli(&args);
+    if !args.null && !output.is_empty() {
+        output.push(b'\n');
     }
+    // File names are written as raw bytes, they may not be valid UTF-8.
+    io::stdout().write_all(&output).expect("Could not write output");
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs::{self, File};
@@ -249,4 +251,509 @@ fn test_dupels_integration_no_read_permission_dir() {
 
     cmd.assert()
         .success();  // Directory is still readable, just check it passes.
+}
+
+#[test]
+fn test_dupels_integration_hash_option() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    for hash in ["md5", "sha1", "sha256", "blake3", "xxh3"] {
+        let mut cmd = Command::cargo_bin("dupels").unwrap();
+        cmd.args(["-o", "--hash", hash, p]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(format!("{}/1.txt", p)))
+            .stdout(predicate::str::contains(format!("{}/2.txt", p)))
+            .stdout(predicate::str::contains(format!("{}/3.txt", p)).not());
+    }
+}
+
+#[test]
+fn test_dupels_integration_invalid_hash_option() {
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--hash", "crc32"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("possible values"));
+}
+
+#[test]
+fn test_dupels_integration_paranoid() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", "--paranoid", p]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}/1.txt", p)))
+        .stdout(predicate::str::contains(format!("{}/more_test_files/5.txt", p)))
+        .stdout(predicate::str::contains(">--").not());
+}
+
+#[test]
+fn test_dupels_integration_format_json() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--format", "json", p]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("["))
+        .stdout(predicate::str::contains(r#""digest": "8b1a9953c4611296a827abf8c47804d7""#))
+        .stdout(predicate::str::contains(r#""size": 5"#))
+        .stdout(predicate::str::contains(format!("{}/1.txt", p)))
+        .stdout(predicate::str::contains(">--").not());
+}
+
+#[test]
+fn test_dupels_integration_format_ndjson() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--format", "ndjson", p]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::function(|out: &str| out.lines().count() == 2))
+        .stdout(predicate::str::contains(r#"{"digest":"8b1a9953c4611296a827abf8c47804d7","size":5,"files":["#));
+}
+
+#[test]
+fn test_dupels_integration_format_csv() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--format", "csv", p]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("group_id,digest,size,path,mtime,root,hard_link_of,alias_of\n"))
+        .stdout(predicate::str::contains(format!("1,8b1a9953c4611296a827abf8c47804d7,5,{}/1.txt,", p)))
+        .stdout(predicate::str::contains(format!("1,8b1a9953c4611296a827abf8c47804d7,5,{}/2.txt,", p)));
+}
+
+#[test]
+fn test_dupels_integration_format_tsv() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--format", "tsv", p]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("group_id\tdigest\tsize\tpath\tmtime\troot\thard_link_of\talias_of\n"))
+        .stdout(predicate::str::contains(format!("1\t8b1a9953c4611296a827abf8c47804d7\t5\t{}/1.txt\t", p)));
+}
+
+#[test]
+fn test_dupels_integration_null_terminated() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/new\nline.txt", p), "Hello").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-0", "-o", p]);
+
+    cmd.assert().success().stdout(format!(
+        "{p}/1.txt\0{p}/2.txt\0{p}/new\nline.txt\0\0",
+        p = p
+    ));
+}
+
+#[test]
+fn test_dupels_integration_null_conflicts_with_format() {
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-0", "--format", "json"]);
+
+    cmd.assert().failure();
+}
+
+
+#[cfg(unix)]
+#[test]
+fn test_dupels_integration_non_utf8_file_name() {
+    use std::os::unix::ffi::OsStrExt;
+    let dir = tempdir().unwrap();
+    let latin1 = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::write(&latin1, "Hello").unwrap();
+    fs::write(dir.path().join("other.txt"), "Hello").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.arg("-o").arg(dir.path());
+
+    let mut expected = latin1.as_os_str().as_bytes().to_vec();
+    expected.push(b'\n');
+    expected.extend_from_slice(dir.path().join("other.txt").as_os_str().as_bytes());
+    expected.push(b'\n');
+    cmd.assert().success().stdout(expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dupels_integration_escape() {
+    use std::os::unix::ffi::OsStrExt;
+    let dir = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    fs::write(dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt")), "Hello").unwrap();
+    fs::write(format!("{}/new\nline.txt", p), "Hello").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--escape", p]);
+
+    cmd.assert().success().stdout(format!("{p}/caf\\xe9.txt\n{p}/new\\nline.txt\n", p = p));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dupels_integration_hard_links() {
+    let dir = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/a.txt", p), "Hello").unwrap();
+    fs::hard_link(format!("{}/a.txt", p), format!("{}/b.txt", p)).unwrap();
+    fs::write(format!("{}/c.txt", p), "Hello").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", p]);
+    cmd.assert().success().stdout(format!("{p}/a.txt\n= {p}/b.txt\n{p}/c.txt\n", p = p));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-0", p]);
+    cmd.assert().success().stdout(format!("{p}/a.txt\0{p}/c.txt\0\0", p = p));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--format", "csv", p]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("1,8b1a9953c4611296a827abf8c47804d7,5,{}/b.txt,", p)))
+        .stdout(predicate::str::contains(format!(",{}/a.txt,\n", p)));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--count-hard-links", p]);
+    cmd.assert().success().stdout(format!("{p}/a.txt\n{p}/b.txt\n{p}/c.txt\n", p = p));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dupels_integration_symlinks() {
+    let dir = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/a.txt", p), "Hello").unwrap();
+    std::os::unix::fs::symlink(format!("{}/a.txt", p), format!("{}/link.txt", p)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--symlinks", "follow", p]);
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--symlinks", "follow", "--format", "ndjson", p]);
+    cmd.assert().success().stdout(predicate::str::contains(format!(
+        r#""aliases":[["{p}/a.txt","{p}/link.txt"]]"#,
+        p = p
+    )));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--symlinks", "follow", p]);
+    cmd.assert().success().stdout(format!("{p}/a.txt\n-> {p}/link.txt\n", p = p));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--symlinks", "list", "-0", p]);
+    cmd.assert().success().stdout(format!("{p}/a.txt\0\0", p = p));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--symlinks", "list", "--format", "csv", p]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("1,,5,{p}/link.txt,", p = p)))
+        .stdout(predicate::str::ends_with(format!(",,,{p}/a.txt\n", p = p)));
+}
+
+#[test]
+fn test_dupels_integration_exclude_include() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", "--exclude", "2.txt", "--exclude", "more_test_files/", p]);
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", "--include", "[12].txt", p]);
+    cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p));
+}
+
+#[test]
+fn test_dupels_integration_invalid_glob() {
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--exclude", "a[b"]);
+
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_dupels_integration_respect_ignore() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/.gitignore", p), "more_test_files/\n2.txt\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", p]);
+    cmd.assert().success().stdout(predicate::str::contains("2.txt"));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", "--respect-ignore", p]);
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn test_dupels_integration_type() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/kick.wav", p), b"RIFF\x24\0\0\0WAVEfmt ").unwrap();
+    fs::write(format!("{}/kick.bak", p), b"RIFF\x24\0\0\0WAVEfmt ").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "-r", "--type", "audio", p]);
+    cmd.assert().success().stdout(format!("{p}/kick.bak\n{p}/kick.wav\n", p = p));
+}
+
+#[test]
+fn test_dupels_integration_invalid_type() {
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--type", "spreadsheet"]);
+
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_dupels_integration_min_max_size() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/big.bin", p), vec![0u8; 2048]).unwrap();
+    fs::write(format!("{}/big copy.bin", p), vec![0u8; 2048]).unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--min-size", "1K", p]);
+    cmd.assert().success().stdout(format!("{p}/big copy.bin\n{p}/big.bin\n", p = p));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--max-size", "1k", p]);
+    cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p));
+}
+
+#[test]
+fn test_dupels_integration_empty_files() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    fs::write(format!("{}/empty1", p), "").unwrap();
+    fs::write(format!("{}/empty2", p), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", p]);
+    cmd.assert().success().stdout(predicate::str::contains("empty").not());
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--empty-files", "list", p]);
+    cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n>--\n{p}/empty1\n{p}/empty2\n", p = p));
+}
+
+#[test]
+fn test_dupels_integration_invalid_size() {
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--min-size", "10X"]);
+
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_dupels_integration_multiple_roots() {
+    let old = setup_test_files();
+    let new = tempdir().unwrap();
+    let p = old.path().to_str().unwrap();
+    let q = new.path().to_str().unwrap();
+    fs::write(format!("{}/copy.txt", q), "Hello World").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    // Files in a group are sorted by path, and the temporary directories have random names.
+    let mut copies = [format!("{}/3.txt", p), format!("{}/copy.txt", q)];
+    copies.sort();
+    let copies = copies.join("\n");
+
+    cmd.args(["-o", p, q]);
+    cmd.assert()
+        .success()
+        .stdout(format!("{p}/1.txt\n{p}/2.txt\n>--\n{copies}\n", p = p, copies = copies));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--cross-root", p, q]);
+    cmd.assert().success().stdout(format!("{}\n", copies));
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--cross-root", "--format", "csv", p, q]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(format!("{}/3.txt,[0-9]+,0,,\n", p)).unwrap())
+        .stdout(predicate::str::is_match(format!("{}/copy.txt,[0-9]+,1,,\n", q)).unwrap());
+}
+
+#[test]
+fn test_dupels_integration_reference() {
+    let library = setup_test_files();
+    let uploads = tempdir().unwrap();
+    let p = library.path().to_str().unwrap();
+    let q = uploads.path().to_str().unwrap();
+    fs::write(format!("{}/copy.txt", q), "Hello World").unwrap();
+    fs::write(format!("{}/new.txt", q), "Not in the library").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--reference", p, q]);
+    cmd.assert().success().stdout(format!("{q}/copy.txt\t{p}/3.txt\n", p = p, q = q));
+}
+
+#[test]
+fn test_dupels_integration_reference_null_terminated() {
+    let library = setup_test_files();
+    let uploads = tempdir().unwrap();
+    let p = library.path().to_str().unwrap();
+    let q = uploads.path().to_str().unwrap();
+    fs::write(format!("{}/copy\twith tab.txt", q), "Hello World").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-0", "--reference", p, q]);
+    cmd.assert().success().stdout(format!("{q}/copy\twith tab.txt\0{p}/3.txt\0\0", p = p, q = q));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dupels_integration_special_file() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    let _listener = std::os::unix::net::UnixListener::bind(format!("{}/socket", p)).unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", p]);
+    cmd.assert()
+        .success()
+        .stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p))
+        .stderr(format!("Skipped socket '{}/socket': not a regular file\n", p));
+}
+
+#[test]
+fn test_dupels_integration_cache() {
+    let dir = setup_test_files();
+    let cache = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("dupels").unwrap();
+        cmd.args(["-o", "--cache-dir", cache.path().to_str().unwrap(), p]);
+        cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p)).stderr("");
+    }
+    assert!(cache.path().join("digests.redb").is_file());
+}
+
+#[test]
+fn test_dupels_integration_snapshot() {
+    let dir = setup_test_files();
+    let snapshots = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    let snapshot = snapshots.path().join("snapshot.json");
+    let snapshot = snapshot.to_str().unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--save", snapshot, p]);
+    cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p));
+
+    fs::write(format!("{}/copy.txt", p), "Hello").unwrap();
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["--since", snapshot, p]);
+    cmd.assert().success().stdout(format!(
+        "changed 8b1a9953c4611296a827abf8c47804d7 5\n  {p}/1.txt\n  {p}/2.txt\n+ {p}/copy.txt\n",
+        p = p
+    ));
+}
+
+#[test]
+fn test_dupels_integration_emit_manifest() {
+    let dir = setup_test_files();
+    let manifests = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    let manifest = manifests.path().join("SHA256SUMS");
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--hash", "sha256", "--emit-manifest", manifest.to_str().unwrap(), p]);
+    cmd.assert().success().stdout(format!("{p}/1.txt\n{p}/2.txt\n", p = p));
+
+    let manifest = fs::read_to_string(&manifest).unwrap();
+    assert_eq!(manifest.lines().count(), 3);
+    assert!(manifest.contains(&format!(
+        "a591a6d40bf420404a011733cfb7b190d62c65bf0bcda32b57b277d9ad9f146e  {}/3.txt\n",
+        p
+    )));
+}
+
+#[test]
+fn test_dupels_integration_from_manifest() {
+    let dir = setup_test_files();
+    let manifests = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    let manifest = manifests.path().join("TAPE42.md5");
+    fs::write(
+        &manifest,
+        "MD5 (/tape/42/hello.txt) = 8b1a9953c4611296a827abf8c47804d7\n\
+         00112233445566778899aabbccddeeff  /tape/42/only.txt\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--cross-root", "--from-manifest", manifest.to_str().unwrap(), p]);
+    cmd.assert().success().stdout(format!("/tape/42/hello.txt\n{p}/1.txt\n{p}/2.txt\n", p = p));
+}
+
+#[test]
+fn test_dupels_integration_from_manifest_and_emit_manifest() {
+    let dir = setup_test_files();
+    let manifests = tempdir().unwrap();
+    let p = dir.path().to_str().unwrap();
+    let tape = manifests.path().join("TAPE42.md5");
+    let emitted = manifests.path().join("MD5SUMS");
+    fs::write(&tape, "8b1a9953c4611296a827abf8c47804d7  /tape/42/hello.txt\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.args(["-o", "--from-manifest", tape.to_str().unwrap(), "--emit-manifest", emitted.to_str().unwrap(), p]);
+    cmd.assert().success().stdout(format!("/tape/42/hello.txt\n{p}/1.txt\n{p}/2.txt\n", p = p));
+
+    // Only the files found in the directory are written, not the file read from the other manifest.
+    let emitted = fs::read_to_string(&emitted).unwrap();
+    assert_eq!(emitted.lines().count(), 3);
+    assert!(!emitted.contains("/tape/42/hello.txt"));
+    assert!(emitted.contains(&format!("8b1a9953c4611296a827abf8c47804d7  {}/1.txt\n", p)));
+}
+
+#[test]
+fn test_dupels_integration_from_manifest_only() {
+    let dir = setup_test_files();
+    let p = dir.path().to_str().unwrap();
+    let manifest = dir.path().join("TAPE42.md5");
+    fs::write(
+        &manifest,
+        "8b1a9953c4611296a827abf8c47804d7  /tape/42/a.txt\n\
+         8b1a9953c4611296a827abf8c47804d7  /tape/43/a.txt\n",
+    )
+    .unwrap();
+
+    // The current directory holds duplicates too, but only the manifest is read.
+    let mut cmd = Command::cargo_bin("dupels").unwrap();
+    cmd.current_dir(p);
+    cmd.args(["--from-manifest", "TAPE42.md5"]);
+    cmd.assert().success().stdout("/tape/42/a.txt\n/tape/43/a.txt\n");
 }
\ No newline at end of file
@@ -2,10 +2,11 @@
 
 use eframe::egui;
 
-use dupels_lib::{Gui, run_gui};
+use dupels_lib::{DupeLsError, DuplicateGroup, Gui, run_gui};
 
 struct DupeLsApp {
-    output: Vec<String>,
+    output: Vec<DuplicateGroup>,
+    errors: Vec<DupeLsError>,
     directory: String,
     all: bool,
     depth: u32,
@@ -16,6 +17,7 @@ impl Default for DupeLsApp {
     fn default() -> Self {
         Self {
             output: Vec::new(),
+            errors: Vec::new(),
             directory: String::new(),
             all: false,
             omit: false,
@@ -30,7 +32,7 @@ impl DupeLsApp {
             "Running dupels with directory: {}, all: {}, depth: {}, omit: {}",
             self.directory, self.all, self.depth, self.omit
         );
-        self.output = run_gui(&Gui {
+        (self.output, self.errors) = run_gui(&Gui {
             directory: self.directory.clone(),
             all: self.all,
             depth: self.depth as usize,
@@ -39,36 +41,38 @@ impl DupeLsApp {
         if self.output.is_empty() {
             println!("No duplicates found.");
         } else {
-            for line in &self.output {
-                println!("{}", line);
+            for group in &self.output {
+                for file in &group.files {
+                    println!("{}", file.path.display());
+                }
             }
         }
+        for err in &self.errors {
+            println!("{}", err);
+        }
+    }
+
+    fn display_errors(&self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("Skipped ({})", self.errors.len()))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.indent("error_indent", |ui| {
+                    for err in &self.errors {
+                        ui.horizontal(|ui| {
+                            ui.label("•");
+                            ui.add(egui::Label::new(err.to_string()).selectable(true));
+                        });
+                    }
+                });
+            });
+        ui.add_space(5.0);
     }
 
     fn display_duplicate_groups(&mut self, ui: &mut egui::Ui) {
-        let separator = "==="; // TODO: This is sus. Ideally the vector shouldn't have the separator.
-        let mut groups = Vec::new();
-        let mut current_group = Vec::new();
-        
-        for line in &self.output {
-            if line == separator {
-                if !current_group.is_empty() {
-                    groups.push(current_group.clone());
-                    current_group.clear();
-                }
-            } else if !line.trim().is_empty() {
-                current_group.push(line.clone());
-            }
-        }
-        
-        if !current_group.is_empty() {  // Edge case for last group.
-            groups.push(current_group);
-        }
-        
         // Display files as groups in a collapsible header.
-        for (group_idx, group) in groups.iter().enumerate() {
-            let header_text = if group.len() > 1 {
-                format!("Group {} ({} files)", group_idx + 1, group.len())
+        for (group_idx, group) in self.output.iter().enumerate() {
+            let header_text = if group.files.len() > 1 {
+                format!("Group {} ({} files)", group_idx + 1, group.files.len())
             } else {
                 format!("Group {} (1 file)", group_idx + 1)
             };
@@ -77,13 +81,25 @@ impl DupeLsApp {
                 .default_open(true)
                 .show(ui, |ui| {
                     ui.indent("group_indent", |ui| {
-                        for file_path in group.iter() {
+                        for file in group.files.iter() {
+                            let file_path = &file.path;
                             ui.horizontal(|ui| {
                                 // Add a bullet point
                                 ui.label("•");
                                 // Make the path selectable and clickable
+                                let mut label = file_path.display().to_string();
+                                match file.hard_links.len() {
+                                    0 => {}
+                                    1 => label.push_str(" (+1 hard link)"),
+                                    n => label.push_str(&format!(" (+{} hard links)", n)),
+                                }
+                                match file.aliases.len() {
+                                    0 => {}
+                                    1 => label.push_str(" (+1 symlink)"),
+                                    n => label.push_str(&format!(" (+{} symlinks)", n)),
+                                }
                                 let response = ui.add(
-                                    egui::Label::new(file_path)
+                                    egui::Label::new(label)
                                         .selectable(true)
                                         .sense(egui::Sense::click())
                                 );
@@ -95,7 +111,7 @@ impl DupeLsApp {
                                 if response.clicked() {
                                     // Open the file/directory in the default system application
                                     if let Err(e) = open::that(file_path) {
-                                        println!("Failed to open {}: {}", file_path, e);
+                                        println!("Failed to open {}: {}", file_path.display(), e);
                                     }
                                 }
                             });
@@ -181,7 +197,7 @@ impl eframe::App for DupeLsApp {
             ui.separator();
             ui.label("Results:");
             
-            if self.output.is_empty() {
+            if self.output.is_empty() && self.errors.is_empty() {
                 ui.label("No results yet. Click 'Run' to find duplicates.");
             } else {
                 // Create a scrollable area that uses remaining available height
@@ -190,6 +206,13 @@ impl eframe::App for DupeLsApp {
                     .max_height(available_height)
                     .auto_shrink(false)
                     .show(ui, |ui| {
+                        // Skipped files are shown even when none could be grouped, e.g. when every file was unreadable.
+                        if !self.errors.is_empty() {
+                            self.display_errors(ui);
+                        }
+                        if self.output.is_empty() {
+                            ui.label("No files found.");
+                        }
                         self.display_duplicate_groups(ui);
                     });
             }
@@ -0,0 +1,259 @@
+use redb::{Database, ReadableDatabase, TableDefinition};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use crate::{ChecksumAlgorithm, Digest, FileEntry};
+
+/// Name of the database file inside the cache directory.
+const CACHE_FILE_NAME: &str = "digests.redb";
+
+/// Length of the size and modification time stored before the digest in an extended attribute.
+const XATTR_HEADER_LEN: usize = 8 + 8 + 4;
+
+/// A store of digests computed by earlier scans.
+///
+/// A stored digest is only returned while the file still has the size, modification time and inode it had
+/// when it was hashed, so a changed file is always hashed again.
+pub trait DigestCache: Send + Sync {
+    /// Returns the digest of `file` if it is stored and the file hasn't changed since.
+    fn get(&self, file: &FileEntry, algorithm: ChecksumAlgorithm) -> Option<Digest>;
+
+    /// Stores the digest of `file`. It may only be written once `flush` is called.
+    fn insert(&self, file: &FileEntry, algorithm: ChecksumAlgorithm, digest: &Digest);
+
+    /// Writes the digests stored since the last call.
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The stat of a file when it was hashed: size, modification time in seconds and nanoseconds, device and inode.
+type Stat = (u64, u64, u32, u64, u64);
+
+/// The stat of `file`, `None` if it has no modification time.
+fn stat(file: &FileEntry) -> Option<Stat> {
+    let modified = file.modified?.duration_since(UNIX_EPOCH).ok()?;
+    let (dev, ino) = file.file_id.unwrap_or_default();
+    Some((file.size, modified.as_secs(), modified.subsec_nanos(), dev, ino))
+}
+
+/// A digest waiting to be written: the path, the hash function, the stat and the digest.
+type PendingDigest = (Vec<u8>, ChecksumAlgorithm, Stat, Digest);
+
+/// A digest cache kept in a redb database, with one table per hash function keyed by path.
+///
+/// Digests are collected in memory and written in a single transaction by `flush`.
+pub struct RedbCache {
+    db: Database,
+    pending: Mutex<Vec<PendingDigest>>,
+}
+
+impl RedbCache {
+    /// The default cache directory, `$XDG_CACHE_HOME/dupels` or the platform equivalent.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("dupels"))
+    }
+
+    /// Opens the cache in `dir`, creating it if needed. If `purge` is set, every stored digest is dropped first.
+    pub fn open(dir: &Path, purge: bool) -> io::Result<RedbCache> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(CACHE_FILE_NAME);
+        if purge {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        let db = Database::create(path).map_err(io::Error::other)?;
+        Ok(RedbCache { db, pending: Mutex::new(Vec::new()) })
+    }
+
+    fn table(algorithm: ChecksumAlgorithm) -> TableDefinition<'static, &'static [u8], (Stat, &'static [u8])> {
+        TableDefinition::new(algorithm.name())
+    }
+
+    fn lookup(&self, key: &[u8], algorithm: ChecksumAlgorithm) -> Result<Option<(Stat, Digest)>, redb::Error> {
+        let read = self.db.begin_read()?;
+        let table = match read.open_table(RedbCache::table(algorithm)) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(table.get(key)?.map(|value| {
+            let (stat, digest) = value.value();
+            (stat, Digest::from(digest.to_vec()))
+        }))
+    }
+
+    fn write_pending(&self) -> Result<(), redb::Error> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let write = self.db.begin_write()?;
+        let mut tables = HashMap::new();
+        for (key, algorithm, stat, digest) in &pending {
+            let table = match tables.entry(*algorithm) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(write.open_table(RedbCache::table(*algorithm))?),
+            };
+            table.insert(key.as_slice(), (*stat, digest.as_ref()))?;
+        }
+        drop(tables);
+        write.commit()?;
+        Ok(())
+    }
+}
+
+impl DigestCache for RedbCache {
+    fn get(&self, file: &FileEntry, algorithm: ChecksumAlgorithm) -> Option<Digest> {
+        let stat = stat(file)?;
+        // A cache that can't be read is treated as empty, the files are hashed instead.
+        let (stored, digest) = self.lookup(file.path.as_os_str().as_encoded_bytes(), algorithm).ok()??;
+        (stored == stat).then_some(digest)
+    }
+
+    fn insert(&self, file: &FileEntry, algorithm: ChecksumAlgorithm, digest: &Digest) {
+        if let Some(stat) = stat(file) {
+            let key = file.path.as_os_str().as_encoded_bytes().to_vec();
+            self.pending.lock().unwrap().push((key, algorithm, stat, digest.clone()));
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.write_pending().map_err(io::Error::other)
+    }
+}
+
+/// A digest cache kept in a `user.dupels.<algorithm>` extended attribute of each file.
+///
+/// The attribute holds the size and modification time of the file next to the digest. Unlike `RedbCache` it
+/// moves with the file, so a file that was renamed or moved within its filesystem isn't hashed again. Files
+/// whose attributes can't be read or written, e.g. on filesystems without extended attributes, are hashed
+/// as if there was no cache.
+pub struct XattrCache;
+
+impl XattrCache {
+    fn name(algorithm: ChecksumAlgorithm) -> String {
+        format!("user.dupels.{}", algorithm.name())
+    }
+
+    /// The size and modification time of the file, big-endian, followed by the digest.
+    fn encode(file: &FileEntry, digest: &Digest) -> Option<Vec<u8>> {
+        let (size, secs, nanos, _, _) = stat(file)?;
+        let mut value = Vec::with_capacity(XATTR_HEADER_LEN + digest.as_ref().len());
+        value.extend_from_slice(&size.to_be_bytes());
+        value.extend_from_slice(&secs.to_be_bytes());
+        value.extend_from_slice(&nanos.to_be_bytes());
+        value.extend_from_slice(digest.as_ref());
+        Some(value)
+    }
+
+    /// Returns the digest in `value` if it was stored for the current size and modification time of `file`.
+    fn decode(file: &FileEntry, value: &[u8]) -> Option<Digest> {
+        let expected = XattrCache::encode(file, &Digest::from(Vec::new()))?;
+        let (header, digest) = value.split_at_checked(XATTR_HEADER_LEN)?;
+        (header == expected && !digest.is_empty()).then(|| Digest::from(digest.to_vec()))
+    }
+}
+
+#[cfg(unix)]
+impl DigestCache for XattrCache {
+    fn get(&self, file: &FileEntry, algorithm: ChecksumAlgorithm) -> Option<Digest> {
+        let value = xattr::get(&file.path, XattrCache::name(algorithm)).ok()??;
+        XattrCache::decode(file, &value)
+    }
+
+    fn insert(&self, file: &FileEntry, algorithm: ChecksumAlgorithm, digest: &Digest) {
+        if let Some(value) = XattrCache::encode(file, digest) {
+            let _ = xattr::set(&file.path, XattrCache::name(algorithm), &value);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl DigestCache for XattrCache {
+    fn get(&self, _file: &FileEntry, _algorithm: ChecksumAlgorithm) -> Option<Digest> {
+        None
+    }
+
+    fn insert(&self, _file: &FileEntry, _algorithm: ChecksumAlgorithm, _digest: &Digest) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    fn file_entry(path: &str, size: u64, modified: u64) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            size,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+            hard_links: Vec::new(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: Some((1, 2)),
+        }
+    }
+
+    #[test]
+    fn test_redb_cache() {
+        let dir = tempdir().unwrap();
+        let digest = Digest::from(vec![1, 2, 3]);
+        let file = file_entry("/archive/a.txt", 5, 100);
+
+        let cache = RedbCache::open(dir.path(), false).unwrap();
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), None);
+        cache.insert(&file, ChecksumAlgorithm::Md5, &digest);
+        cache.flush().unwrap();
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), Some(digest.clone()));
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Sha256), None);
+        assert_eq!(cache.get(&file_entry("/archive/a.txt", 5, 101), ChecksumAlgorithm::Md5), None);
+        assert_eq!(cache.get(&file_entry("/archive/a.txt", 6, 100), ChecksumAlgorithm::Md5), None);
+        drop(cache);
+
+        let cache = RedbCache::open(dir.path(), false).unwrap();
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), Some(digest));
+        drop(cache);
+
+        let cache = RedbCache::open(dir.path(), true).unwrap();
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_xattr_cache() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "Hello").unwrap();
+        let mut file = FileEntry::new(path.clone(), &fs::metadata(&path).unwrap());
+        let digest = Digest::from(vec![1, 2, 3]);
+
+        let cache = XattrCache;
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), None);
+        cache.insert(&file, ChecksumAlgorithm::Md5, &digest);
+        if xattr::get(&path, "user.dupels.md5").ok().flatten().is_none() {
+            return; // The filesystem doesn't support user extended attributes.
+        }
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), Some(digest.clone()));
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Sha256), None);
+
+        // The digest moves with the file.
+        let moved = dir.path().join("b.txt");
+        fs::rename(&path, &moved).unwrap();
+        file.path = moved;
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), Some(digest));
+
+        file.modified = file.modified.map(|modified| modified + Duration::from_secs(1));
+        assert_eq!(cache.get(&file, ChecksumAlgorithm::Md5), None);
+    }
+}
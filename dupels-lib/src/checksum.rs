@@ -0,0 +1,177 @@
+use clap::ValueEnum;
+use sha1::Digest as _;
+use std::fmt;
+
+/// The hash function used to compute file checksums.
+///
+/// MD5 remains the default for compatibility with previous releases. `Xxh3` is a fast
+/// non-cryptographic 128-bit hash and should only be used on trusted data.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+    Xxh3,
+}
+
+impl ChecksumAlgorithm {
+    /// Returns a fresh hasher for this algorithm.
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        match self {
+            ChecksumAlgorithm::Md5 => Box::new(md5::Context::new()),
+            ChecksumAlgorithm::Sha1 => Box::new(sha1::Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Box::new(sha2::Sha256::new()),
+            ChecksumAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+            ChecksumAlgorithm::Xxh3 => Box::new(xxhash_rust::xxh3::Xxh3::new()),
+        }
+    }
+
+    /// The lowercase name of the algorithm, as accepted by the `--hash` option.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An incremental hash function that can be fed a file in chunks.
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]);
+    fn finish(self: Box<Self>) -> Digest;
+}
+
+impl Hasher for md5::Context {
+    fn update(&mut self, data: &[u8]) {
+        self.consume(data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest((*self).compute().to_vec())
+    }
+}
+
+impl Hasher for sha1::Sha1 {
+    fn update(&mut self, data: &[u8]) {
+        sha1::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest((*self).finalize().to_vec())
+    }
+}
+
+impl Hasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest((*self).finalize().to_vec())
+    }
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest(self.finalize().as_bytes().to_vec())
+    }
+}
+
+impl Hasher for xxhash_rust::xxh3::Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        xxhash_rust::xxh3::Xxh3::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest(self.digest128().to_be_bytes().to_vec())
+    }
+}
+
+/// The raw bytes of a computed checksum. Formats as lowercase hex.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest(Vec<u8>);
+
+impl From<Vec<u8>> for Digest {
+    fn from(bytes: Vec<u8>) -> Digest {
+        Digest(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::LowerHex for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}", self)
+    }
+}
+
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Digest({:x})", self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex_digest(algorithm: ChecksumAlgorithm, data: &[u8]) -> String {
+        let mut hasher = algorithm.hasher();
+        hasher.update(data);
+        format!("{:x}", hasher.finish())
+    }
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(hex_digest(ChecksumAlgorithm::Md5, b"Hello"), "8b1a9953c4611296a827abf8c47804d7");
+        assert_eq!(hex_digest(ChecksumAlgorithm::Sha1, b"Hello"), "f7ff9e8b7bb2e09b70935a5d785e0cc5d9d0abf0");
+        assert_eq!(
+            hex_digest(ChecksumAlgorithm::Sha256, b"Hello"),
+            "185f8db32271fe25f561a6fc938b2e264306ec304eda518007d1764826381969"
+        );
+    }
+
+    #[test]
+    fn test_digest_lengths() {
+        assert_eq!(hex_digest(ChecksumAlgorithm::Blake3, b"Hello").len(), 64);
+        assert_eq!(hex_digest(ChecksumAlgorithm::Xxh3, b"Hello").len(), 32);
+    }
+
+    #[test]
+    fn test_chunked_update_matches_single_update() {
+        for algorithm in ChecksumAlgorithm::value_variants() {
+            let mut hasher = algorithm.hasher();
+            hasher.update(b"Hello ");
+            hasher.update(b"World");
+            assert_eq!(format!("{:x}", hasher.finish()), hex_digest(*algorithm, b"Hello World"));
+        }
+    }
+}
@@ -1,13 +1,19 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use globset::Glob;
+
+use crate::filter::{parse_glob, parse_size};
+use crate::{ChecksumAlgorithm, EmptyFilePolicy, FileType, OutputFormat, SymlinkPolicy, DEFAULT_SAMPLE_SIZE};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
-    /// Displays the name of files contained within a directory.
-    /// If no operand is given, the contents of the current directory are displayed.
-    pub file: Option<PathBuf>,
+    /// Displays the name of files contained within the directories.
+    /// If no operand is given, the contents of the current directory are displayed, unless --from-manifest is given.
+    /// Duplicates are found across all of the given directories.
+    #[arg(verbatim_doc_comment)]
+    pub file: Vec<PathBuf>,
 
     /// Include directory entries whose names begin with a dot (.)
     #[clap(short, action)]
@@ -35,4 +41,152 @@ pub struct Cli {
     /// The default is the number of logical cores on the machine with a hard cap of 32 threads.
     #[clap(long, default_value = None, verbatim_doc_comment)]
     pub max_threads: Option<usize>,
+
+    /// Specify the maximum number of threads used to walk the directories, separate from the threads hashing files.
+    /// The default is the number of logical cores on the machine with a hard cap of 32 threads.
+    #[clap(long, default_value = None, verbatim_doc_comment)]
+    pub walk_threads: Option<usize>,
+
+    /// Specify the hash function used to compute file checksums.
+    /// xxh3 is a fast non-cryptographic hash, only use it on trusted data.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub hash: ChecksumAlgorithm,
+
+    /// Specify the size in KiB of the head and tail sample that is compared before hashing whole files.
+    /// Set to 0 to always hash whole files.
+    #[arg(long, value_name = "KIB", default_value_t = DEFAULT_SAMPLE_SIZE, verbatim_doc_comment)]
+    pub sample_size: usize,
+
+    /// Compare files with matching checksums byte-for-byte before grouping them.
+    /// Slower, but guarantees that every group only contains identical files.
+    #[clap(long, alias = "verify", action, verbatim_doc_comment)]
+    pub paranoid: bool,
+
+    /// Specify the output format.
+    /// json and ndjson print one object per group holding its digest, file size, paths and the root of each path.
+    /// csv and tsv print one row per file: group_id, digest, size, path, mtime, root, hard_link_of and alias_of.
+    /// Roots are numbered from 0 in the order the directories were given, followed by --reference and --from-manifest.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub format: OutputFormat,
+
+    /// Terminate each file name with a NUL character instead of a newline, and each group with an extra NUL.
+    /// Use with xargs -0 when file names may contain newlines. Only applies to the text format.
+    #[clap(short = '0', long, action, conflicts_with = "format", verbatim_doc_comment)]
+    pub null: bool,
+
+    /// Escape backslashes, control characters and invalid UTF-8 in file names, e.g. a newline is printed as \n.
+    /// By default file names are printed as raw bytes.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub escape: bool,
+
+    /// List hard links to the same file as duplicates of each other.
+    /// By default each file is read once and listed under the first of its links, followed by its other links prefixed with "= ".
+    /// The csv and tsv formats name the first link in the hard_link_of column instead, and -0 leaves the other links out.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub count_hard_links: bool,
+
+    /// Specify how symbolic links are treated.
+    /// ignore skips them, follow reads the files and directories they point at, list shows links to files without reading them.
+    /// Symlinks to the same file are listed once, as aliases of that file: after it prefixed with "-> " in the text format,
+    /// with the path of the file in the alias_of column of csv and tsv, and under aliases in json. -0 leaves them out.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub symlinks: SymlinkPolicy,
+
+    /// Skip files and directories matching the glob. Excluded directories are not walked.
+    /// The glob is matched against the name and the path relative to the searched directory. Can be repeated.
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob, verbatim_doc_comment)]
+    pub exclude: Vec<Glob>,
+
+    /// Only list files matching the glob. Directories are still walked. Can be repeated.
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob, verbatim_doc_comment)]
+    pub include: Vec<Glob>,
+
+    /// Skip files and directories ignored by .gitignore, .ignore and .git/info/exclude files, and by the global git excludes file.
+    /// Ignore files in the parent directories of each directory are applied up to the root of its git repository.
+    /// The .git directory is skipped as well.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub respect_ignore: bool,
+
+    /// Only list files of the given type, found by their extension or else by their first bytes.
+    /// Can be repeated or given as a comma separated list.
+    #[arg(long = "type", value_name = "TYPE", value_enum, value_delimiter = ',', verbatim_doc_comment)]
+    pub types: Vec<FileType>,
+
+    /// Skip files smaller than SIZE. Accepts a number of bytes or a K, M, G or T suffix, e.g. 10K or 4M.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, verbatim_doc_comment)]
+    pub min_size: Option<u64>,
+
+    /// Skip files larger than SIZE. Accepts a number of bytes or a K, M, G or T suffix, e.g. 10K or 4M.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, verbatim_doc_comment)]
+    pub max_size: Option<u64>,
+
+    /// Specify what to do with empty files.
+    /// skip leaves them out, list shows them together in a group of their own.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub empty_files: EmptyFilePolicy,
+
+    /// Only display groups with files from more than one of the given directories.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub cross_root: bool,
+
+    /// Search DIR as a trusted reference directory. Files in it are never displayed on their own,
+    /// only files that already exist in a reference directory are displayed, each followed by a tab and the
+    /// reference file with the same contents. With -0 the reference file is written as a field of its own after each file.
+    /// Can be repeated.
+    #[arg(long, value_name = "DIR", verbatim_doc_comment)]
+    pub reference: Vec<PathBuf>,
+
+    /// Do not descend into directories on a different filesystem than the given directory, e.g. /proc or network mounts.
+    #[clap(short = 'x', long, action, verbatim_doc_comment)]
+    pub one_file_system: bool,
+
+    /// Keep the checksums of hashed files in a cache in $XDG_CACHE_HOME/dupels.
+    /// Files whose size, modification time and inode haven't changed since are not hashed again.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub cache: bool,
+
+    /// Keep the checksum cache in DIR instead. Implies --cache.
+    #[arg(long, value_name = "DIR", verbatim_doc_comment)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Empty the checksum cache before searching, so every file is hashed again. Implies --cache.
+    #[clap(long, action, verbatim_doc_comment)]
+    pub purge_cache: bool,
+
+    /// Keep the checksum of each hashed file in its user.dupels.<HASH> extended attribute instead, next to its modification time.
+    /// Unlike --cache the checksum moves with the file when it is renamed. Files that don't allow extended attributes are hashed every time.
+    #[clap(long, action, conflicts_with_all = ["cache", "cache_dir", "purge_cache"], verbatim_doc_comment)]
+    pub xattr_cache: bool,
+
+    /// Save the groups of duplicates to FILE as a JSON snapshot, to compare a later search against with --since.
+    /// Every group found is saved, including those hidden by --omit, --cross-root and --reference, but not the files of --from-manifest.
+    #[arg(long, value_name = "FILE", verbatim_doc_comment)]
+    pub save: Option<PathBuf>,
+
+    /// Only display the groups that changed since the snapshot in FILE: new groups, groups that gained or lost files, and resolved groups.
+    /// In the text format added files are prefixed with +, removed files with - and kept files with two spaces.
+    /// A file that is left alone in a resolved group is listed as kept, only the files that were deleted or changed are removed.
+    /// Can be combined with --save on the same FILE to report the changes since the last search.
+    #[arg(long, value_name = "FILE", conflicts_with = "null", verbatim_doc_comment)]
+    pub since: Option<PathBuf>,
+
+    /// Write the checksum of every file to FILE in the format of md5sum and sha256sum, to check them later with md5sum -c.
+    /// Every file is hashed in full, including unique files. Use --hash to pick the tool the manifest is for.
+    /// Only the files found in the directories are written, not those read with --from-manifest.
+    #[arg(long, value_name = "FILE", verbatim_doc_comment)]
+    pub emit_manifest: Option<PathBuf>,
+
+    /// Group the files listed in the checksum manifest FILE with the files in the directories, without reading them.
+    /// Accepts the format of md5sum and sha256sum, and the BSD format of md5sum --tag. The checksums must match --hash.
+    /// Every file in the directories is hashed in full to compare it against the manifest. Can be repeated.
+    /// Without directories only the files of the manifests are grouped, the current directory is not searched.
+    #[arg(long, value_name = "FILE", verbatim_doc_comment)]
+    pub from_manifest: Vec<PathBuf>,
+}
+
+impl Default for Cli {
+    /// The options as parsed from a command line without arguments, so every default matches clap's.
+    fn default() -> Self {
+        Cli::parse_from(["dupels"])
+    }
 }
@@ -1,215 +1,1421 @@
-use crossbeam_channel::{unbounded, Sender};
-use md5;
-use num_cpus;
+use clap::ValueEnum;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use std::{
-    collections::HashMap,
-    env, fs,
-    io::Read,
+    collections::{HashMap, HashSet, VecDeque},
+    env,
+    ffi::OsStr,
+    fs,
+    hash::Hash,
+    iter,
+    io::{self, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
     thread,
 };
 
-use crate::{MAX_THREAD_LIMIT, CHECKSUM_READ_BUFFER_SIZE};
+use crate::manifest;
+use crate::output::{self, path_to_bytes, path_to_string, OutputFormat};
+use crate::{ChecksumAlgorithm, Digest, DigestCache, GroupChange, RedbCache, Snapshot, XattrCache, DupeLsError, DuplicateGroup, FileEntry, FileType, PathFilter, DEFAULT_DEPTH, DEFAULT_SAMPLE_SIZE, MAX_THREAD_LIMIT, CHECKSUM_READ_BUFFER_SIZE};
+
+/// How the directory walk treats symbolic links.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Skip symlinks to files and directories.
+    #[default]
+    Ignore,
+    /// Follow symlinks to files and directories. Links back to a parent directory are not followed.
+    Follow,
+    /// List symlinks to files without reading their targets. Links to directories are not followed.
+    List,
+}
+
+/// The directories above the one being walked, cloned into each subdirectory.
+///
+/// # Fields
+/// - `root`: The index of the root directory in `base_paths`, followed by `reference_paths`.
+/// - `reference`: Whether the root directory is a reference directory.
+/// - `device`: The filesystem of the root directory, directories on other filesystems are skipped if it is set.
+/// - `ancestors`: The inodes of the directories, used to stop symlink loops.
+/// - `ignores`: The ignore files found in the directories, the closest last.
+/// - `parent_ignores`: The ignore files above the root directory up to the root of its git repository, each with
+///   the path of the root directory relative to the directory of the file, the closest last.
+#[derive(Clone)]
+struct WalkState {
+    root: usize,
+    reference: bool,
+    device: Option<u64>,
+    ancestors: Vec<(u64, u64)>,
+    ignores: Vec<Arc<Gitignore>>,
+    parent_ignores: Arc<Vec<(PathBuf, Gitignore)>>,
+}
+
+/// A directory waiting to be walked. `linked` is set once the walk has followed a symlink to a directory.
+struct DirJob {
+    path: PathBuf,
+    depth: usize,
+    linked: bool,
+    state: WalkState,
+}
+
+/// The directories waiting to be walked by the threads of `walk_in_parallel`.
+///
+/// `pending` counts the directories queued or being walked, the walk is done once it drops to 0. A thread that
+/// finds nothing to steal sleeps on `idle` until a directory is queued or the walk is done.
+struct WalkQueue {
+    injector: Injector<DirJob>,
+    stealers: Vec<Stealer<DirJob>>,
+    pending: AtomicUsize,
+    lock: Mutex<()>,
+    idle: Condvar,
+}
+
+impl WalkQueue {
+    /// Queues a subdirectory on the thread's own queue and wakes a thread to steal it.
+    fn push(&self, local: &Worker<DirJob>, job: DirJob) {
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        local.push(job);
+        let _lock = self.lock.lock().unwrap();
+        self.idle.notify_one();
+    }
+
+    /// Takes a directory from the thread's own queue, or else from the roots or another thread.
+    fn find_job(&self, local: &Worker<DirJob>) -> Option<DirJob> {
+        local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.injector
+                    .steal_batch_and_pop(local)
+                    .or_else(|| self.stealers.iter().map(Stealer::steal).collect::<Steal<DirJob>>())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+
+    /// Sleeps until there may be a directory to steal. Returns `false` once the walk is done.
+    fn wait(&self) -> bool {
+        let mut lock = self.lock.lock().unwrap();
+        loop {
+            if self.pending.load(Ordering::Acquire) == 0 {
+                return false;
+            }
+            if !self.injector.is_empty() || self.stealers.iter().any(|stealer| !stealer.is_empty()) {
+                return true;
+            }
+            lock = self.idle.wait(lock).unwrap();
+        }
+    }
+
+    /// Marks a directory as walked, and wakes every thread once it was the last one.
+    fn finish(&self) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let _lock = self.lock.lock().unwrap();
+            self.idle.notify_all();
+        }
+    }
+}
+
+/// Calls `WalkQueue::finish` when dropped, so a directory whose walk panicked doesn't keep the other threads
+/// waiting for it.
+struct Walking<'a>(&'a WalkQueue);
+
+impl Drop for Walking<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// Written before a hard link in the text format, to tell it apart from a duplicate.
+const HARD_LINK_PREFIX: &str = "= ";
+/// Written before a symlink in the text format, to tell it apart from a duplicate.
+const ALIAS_PREFIX: &str = "-> ";
+
+/// A line of the text format. `link` is the prefix of a hard link or symlink of the file on the line before.
+struct TextLine {
+    path: PathBuf,
+    reference: Option<PathBuf>,
+    link: Option<&'static str>,
+}
+
+/// What to do with empty files. They all have the same contents, so listing them as duplicates is rarely useful.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyFilePolicy {
+    /// Leave empty files out.
+    #[default]
+    Skip,
+    /// List empty files together in a group of their own, after the other groups.
+    List,
+}
 
 /// Configuration for the DupeLs duplicate file finder.
 ///
 /// # Fields
-/// - `base_path`: The root directory to start searching for duplicates. If `None`, the current directory is used.
+/// - `base_paths`: The root directories to search for duplicates. If empty, the current directory is used unless
+///   `from_manifests` is set.
 /// - `track_dot_files`: If `true`, include files and directories whose names begin with a dot (`.`).
 /// - `recursive`: If `true`, search subdirectories recursively up to `depth`.
 /// - `depth`: The maximum recursion depth for directory traversal.
 /// - `seperator`: String used to separate groups of duplicate files in the output.
 /// - `omit`: If `true`, omit groups that contain only a single file from the output.
 /// - `max_threads`: The maximum number of threads to use for processing files.
+/// - `hash`: The hash function used to compute file checksums.
+/// - `sample_size`: Size in KiB of the head and tail sample hashed before a full checksum is computed. `0` disables sampling.
+/// - `verify`: If `true`, compare the contents of files with matching checksums byte-for-byte.
+/// - `format`: The format used by `DupeLs::get_output_string`.
+/// - `null_terminated`: If `true`, the text format terminates each path with `\0` and each group with an extra `\0`.
+/// - `escape`: If `true`, backslashes, control characters and invalid UTF-8 in file names are escaped in the output.
+/// - `count_hard_links`: If `true`, hard links to the same file are listed as duplicates of each other. Otherwise only
+///   the first link is listed and the others are kept in its `hard_links`.
+/// - `symlinks`: How symlinks are treated by the walk. Symlinks to the same file are always listed as `aliases` of it.
+/// - `filter`: The exclude and include globs applied to the files and directories found by the walk.
+/// - `respect_ignore`: If `true`, skip files and directories ignored by `.gitignore`, `.ignore` and `.git/info/exclude`
+///   files found by the walk or in the parent directories of a root up to its git repository, and by the global git
+///   excludes file.
+/// - `types`: If not empty, only files of these types are listed.
+/// - `min_size`: Files smaller than this many bytes are skipped by the walk.
+/// - `max_size`: If set, files larger than this many bytes are skipped by the walk.
+/// - `empty_files`: Whether empty files are skipped or listed in a group of their own.
+/// - `cross_root`: If `true`, only list groups with files from more than one of the `base_paths`.
+/// - `reference_paths`: Trusted directories that are searched like `base_paths`, but whose files are never listed on
+///   their own. If not empty, only files that have a duplicate in a reference directory are listed.
+/// - `one_file_system`: If `true`, directories on a different filesystem than their root directory are not walked.
+/// - `walk_threads`: The maximum number of threads used to walk the directories, separate from `max_threads`.
+/// - `cache_dir`: If set, checksums are kept in a `RedbCache` in this directory and reused for files whose size,
+///   modification time and inode haven't changed.
+/// - `purge_cache`: If `true`, the cache in `cache_dir` is emptied before searching.
+/// - `xattr_cache`: If `true`, checksums are kept in an `XattrCache` on each file instead, `cache_dir` is ignored.
+/// - `save_snapshot`: If set, the groups found are saved to this file as a `Snapshot`, before any output filter.
+/// - `since_snapshot`: If set, the output only lists the groups that changed since the `Snapshot` in this file.
+/// - `manifest`: If set, every file is hashed in full and its checksum is written to this file in the format of
+///   `md5sum`, so it can be checked later with `md5sum -c` or the tool matching `hash`. Files read from
+///   `from_manifests` are not written.
+/// - `from_manifests`: Checksum manifests whose files are grouped with the files found by the walk, without
+///   reading them. Every file found by the walk is hashed in full to compare it against the manifests. The
+///   `base_paths` are walked, but the current directory isn't searched if there are none.
 ///
 /// # Example
 /// ```rust
-/// use dupels_lib::DupeLsConfig;
+/// use dupels_lib::{ChecksumAlgorithm, DupeLsConfig, EmptyFilePolicy, FileType, OutputFormat, PathFilter, SymlinkPolicy};
 /// use std::path::PathBuf;
 ///
 /// let config = DupeLsConfig {
-///     base_path: Some(PathBuf::from("/tmp")),
+///     base_paths: vec![PathBuf::from("/tmp")],
 ///     track_dot_files: true,
 ///     recursive: true,
 ///     depth: 3,
 ///     seperator: ">--".to_string(),
 ///     omit: false,
 ///     max_threads: Some(4),
+///     hash: ChecksumAlgorithm::Sha256,
+///     sample_size: 4,
+///     verify: false,
+///     format: OutputFormat::Json,
+///     null_terminated: false,
+///     escape: false,
+///     count_hard_links: false,
+///     symlinks: SymlinkPolicy::Ignore,
+///     filter: PathFilter::default(),
+///     respect_ignore: false,
+///     types: vec![FileType::Audio],
+///     min_size: 1024,
+///     max_size: None,
+///     empty_files: EmptyFilePolicy::Skip,
+///     cross_root: false,
+///     reference_paths: Vec::new(),
+///     one_file_system: false,
+///     walk_threads: Some(4),
+///     cache_dir: None,
+///     purge_cache: false,
+///     xattr_cache: false,
+///     save_snapshot: None,
+///     since_snapshot: None,
+///     manifest: None,
+///     from_manifests: Vec::new(),
 /// };
 /// ```
 pub struct DupeLsConfig {
-    pub base_path: Option<PathBuf>,
+    pub base_paths: Vec<PathBuf>,
     pub track_dot_files: bool,
     pub recursive: bool,
     pub depth: usize,
     pub seperator: String,
     pub omit: bool,
     pub max_threads: Option<usize>,
+    pub hash: ChecksumAlgorithm,
+    pub sample_size: usize,
+    pub verify: bool,
+    pub format: OutputFormat,
+    pub null_terminated: bool,
+    pub escape: bool,
+    pub count_hard_links: bool,
+    pub symlinks: SymlinkPolicy,
+    pub filter: PathFilter,
+    pub respect_ignore: bool,
+    pub types: Vec<FileType>,
+    pub min_size: u64,
+    pub max_size: Option<u64>,
+    pub empty_files: EmptyFilePolicy,
+    pub cross_root: bool,
+    pub reference_paths: Vec<PathBuf>,
+    pub one_file_system: bool,
+    pub walk_threads: Option<usize>,
+    pub cache_dir: Option<PathBuf>,
+    pub purge_cache: bool,
+    pub xattr_cache: bool,
+    pub save_snapshot: Option<PathBuf>,
+    pub since_snapshot: Option<PathBuf>,
+    pub manifest: Option<PathBuf>,
+    pub from_manifests: Vec<PathBuf>,
+}
+
+impl Default for DupeLsConfig {
+    fn default() -> Self {
+        DupeLsConfig {
+            base_paths: Vec::new(),
+            track_dot_files: false,
+            recursive: false,
+            depth: DEFAULT_DEPTH,
+            seperator: ">--".to_string(),
+            omit: false,
+            max_threads: None,
+            hash: ChecksumAlgorithm::default(),
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            verify: false,
+            format: OutputFormat::default(),
+            null_terminated: false,
+            escape: false,
+            count_hard_links: false,
+            symlinks: SymlinkPolicy::default(),
+            filter: PathFilter::default(),
+            respect_ignore: false,
+            types: Vec::new(),
+            min_size: 0,
+            max_size: None,
+            empty_files: EmptyFilePolicy::default(),
+            cross_root: false,
+            reference_paths: Vec::new(),
+            one_file_system: false,
+            walk_threads: None,
+            cache_dir: None,
+            purge_cache: false,
+            xattr_cache: false,
+            save_snapshot: None,
+            since_snapshot: None,
+            manifest: None,
+            from_manifests: Vec::new(),
+        }
+    }
 }
 
 impl DupeLsConfig {
-    pub fn resolved_base_path(&self) -> PathBuf {
-        match &self.base_path {
-            Some(path) => path.clone(),
-            None => env::current_dir().expect("Could not get current directory"),
+    /// The directories to search. If none are given the current directory is searched, unless the files are
+    /// only compared against `from_manifests`.
+    pub fn resolved_base_paths(&self) -> Vec<PathBuf> {
+        if self.base_paths.is_empty() && self.from_manifests.is_empty() {
+            vec![env::current_dir().expect("Could not get current directory")]
+        } else {
+            self.base_paths.clone()
         }
     }
 
     pub fn resolved_max_threads(&self) -> usize {
         self.max_threads
-            .unwrap_or_else(|| num_cpus::get())
-            .max(1) // Ensure at least one thread is used
-            .min(MAX_THREAD_LIMIT) // Limit to MAX_THREAD_LIMIT
+            .unwrap_or_else(num_cpus::get)
+            .clamp(1, MAX_THREAD_LIMIT) // Use at least one thread and at most MAX_THREAD_LIMIT
+    }
+
+    pub fn resolved_walk_threads(&self) -> usize {
+        self.walk_threads.unwrap_or_else(num_cpus::get).clamp(1, MAX_THREAD_LIMIT)
     }
 }
 
 /// A struct for finding duplicate files.
 /// # Fields
-/// - `base_path`: The base path to start searching for duplicates.
+/// - `base_paths`: The root directories to search for duplicates, each file records the index of its root.
 /// - `track_dot_files`: Whether to track dot files (hidden files).
 /// - `recursive`: Whether to search recursively in subdirectories.
 /// - `depth`: The depth of recursion.
 /// - `seperator`: The string used to separate duplicate file groups in output.
 /// - `omit`: Whether to omit single files from the output.
 /// - `max_threads`: The maximum number of threads to use for processing files.
-/// - `entries`: A map of checksums to file paths.
+/// - `hash`: The hash function used to compute file checksums.
+/// - `sample_size`: Size in bytes of the head and tail sample hashed before a full checksum is computed.
+/// - `verify`: Whether to compare files with matching checksums byte-for-byte.
+/// - `format`: The format used by `get_output_string`.
+/// - `null_terminated`: Whether the text format terminates paths and groups with `\0` instead of using newlines.
+/// - `escape`: Whether file names are escaped in the output instead of written as raw bytes.
+/// - `count_hard_links`: Whether hard links to the same file are listed as duplicates of each other.
+/// - `symlinks`: How symlinks are treated by the walk.
+/// - `filter`: Decides which files and directories are walked.
+/// - `respect_ignore`: Whether ignore files are applied by the walk.
+/// - `global_ignore`: The rules of the global git excludes file, empty unless `respect_ignore` is set.
+/// - `types`: The file types to list, all files are listed if empty.
+/// - `min_size`, `max_size`: The range of file sizes in bytes to list.
+/// - `empty_files`: Whether empty files are skipped or listed in a group of their own.
+/// - `cross_root`: Whether only groups spanning more than one root are listed.
+/// - `reference_paths`: The trusted directories, their roots are numbered after `base_paths`.
+/// - `one_file_system`: Whether the walk stays on the filesystem of each root directory.
+/// - `walk_threads`: The number of threads walking the directories.
+/// - `cache_dir`, `cache`: The checksum cache and its directory, `None` if no cache is used.
+/// - `save_snapshot`, `since_snapshot`: The snapshot files to write and to compare against.
+/// - `changes`: The groups that changed since `since_snapshot`, replacing the groups in the output if set.
+/// - `manifest`: The checksum manifest to write, if set every file is hashed.
+/// - `from_manifests`: The checksum manifests to read, their files are numbered as roots after `reference_paths`.
+/// - `entries`: A map of checksums to files.
+/// - `collisions`: Groups split off an entry by verification, their checksum matches but their contents differ.
+/// - `unhashed`: Files whose size or sample is unique, these can't have a duplicate so they are never fully hashed.
+/// - `empty`: Empty files, only collected if `empty_files` is `EmptyFilePolicy::List`.
+/// - `error_s`, `error_r`: Channel used by the walk and worker threads to report skipped files and directories.
+/// - `errors`: The errors collected by the last call to `parse`, sorted by path.
 pub struct DupeLs {
-    base_path: PathBuf,
+    base_paths: Vec<PathBuf>,
     track_dot_files: bool,
     recursive: bool,
     depth: usize,
     seperator: String,
     omit: bool,
     max_threads: usize,
-    entries: Arc<Mutex<HashMap<md5::Digest, Vec<String>>>>,
+    hash: ChecksumAlgorithm,
+    sample_size: u64,
+    verify: bool,
+    format: OutputFormat,
+    null_terminated: bool,
+    escape: bool,
+    count_hard_links: bool,
+    symlinks: SymlinkPolicy,
+    filter: PathFilter,
+    respect_ignore: bool,
+    global_ignore: Gitignore,
+    types: Vec<FileType>,
+    min_size: u64,
+    max_size: Option<u64>,
+    empty_files: EmptyFilePolicy,
+    cross_root: bool,
+    reference_paths: Vec<PathBuf>,
+    one_file_system: bool,
+    walk_threads: usize,
+    cache_dir: Option<PathBuf>,
+    cache: Option<Arc<dyn DigestCache>>,
+    save_snapshot: Option<PathBuf>,
+    since_snapshot: Option<PathBuf>,
+    changes: Option<Vec<GroupChange>>,
+    manifest: Option<PathBuf>,
+    from_manifests: Vec<PathBuf>,
+    entries: Arc<Mutex<HashMap<Digest, Vec<FileEntry>>>>,
+    collisions: Vec<(Digest, Vec<FileEntry>)>,
+    unhashed: Vec<FileEntry>,
+    empty: Vec<FileEntry>,
+    error_s: Sender<DupeLsError>,
+    error_r: Receiver<DupeLsError>,
+    errors: Vec<DupeLsError>,
 }
 
 impl DupeLs {
     pub fn new(config: DupeLsConfig) -> DupeLs {
-
+        let (error_s, error_r) = unbounded();
+        let cache = if config.xattr_cache {
+            Some(Arc::new(XattrCache) as Arc<dyn DigestCache>)
+        } else {
+            config.cache_dir.as_ref().and_then(|dir| match RedbCache::open(dir, config.purge_cache) {
+                Ok(cache) => Some(Arc::new(cache) as Arc<dyn DigestCache>),
+                Err(e) => {
+                    let _ = error_s.send(DupeLsError::Cache { path: dir.clone(), source: e });
+                    None
+                }
+            })
+        };
         DupeLs {
-            base_path: config.resolved_base_path(),
+            base_paths: config.resolved_base_paths(),
             max_threads: config.resolved_max_threads(),
+            walk_threads: config.resolved_walk_threads(),
             track_dot_files: config.track_dot_files,
             recursive: config.recursive,
             depth: config.depth + 1, // Add 1 to depth to account for the initial directory
             seperator: config.seperator,
             omit: config.omit,
+            hash: config.hash,
+            // A sample larger than any file disables sampling, so a huge size saturates instead of overflowing.
+            sample_size: (config.sample_size as u64).saturating_mul(1024),
+            verify: config.verify,
+            format: config.format,
+            null_terminated: config.null_terminated,
+            escape: config.escape,
+            count_hard_links: config.count_hard_links,
+            symlinks: config.symlinks,
+            filter: config.filter,
+            respect_ignore: config.respect_ignore,
+            global_ignore: if config.respect_ignore { Gitignore::global().0 } else { Gitignore::empty() },
+            types: config.types,
+            min_size: config.min_size,
+            max_size: config.max_size,
+            empty_files: config.empty_files,
+            cross_root: config.cross_root,
+            reference_paths: config.reference_paths,
+            one_file_system: config.one_file_system,
+            cache_dir: config.cache_dir,
+            cache,
+            save_snapshot: config.save_snapshot,
+            since_snapshot: config.since_snapshot,
+            changes: None,
+            manifest: config.manifest,
+            from_manifests: config.from_manifests,
             entries: Arc::new(Mutex::new(HashMap::new())),
+            collisions: Vec::new(),
+            unhashed: Vec::new(),
+            empty: Vec::new(),
+            error_s,
+            error_r,
+            errors: Vec::new(),
         }
     }
 
-    fn get_sorted_checksums(&self) -> Vec<md5::Digest> {
+    /// The directories to walk, `base_paths` followed by `reference_paths`.
+    fn get_roots(&self) -> impl Iterator<Item = &PathBuf> {
+        self.base_paths.iter().chain(&self.reference_paths)
+    }
+
+    fn get_sorted_checksums(&self) -> Vec<Digest> {
         let map = self.entries.lock().unwrap();
         let mut checksums: Vec<_> = map.keys().cloned().collect();
         checksums.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
         checksums
     }
 
-    pub fn get_output_vec(&self) -> Vec<String> {
+    /// Returns the groups of files found by `parse`, ordered by checksum. Groups split off by verification
+    /// follow, then files that were never hashed and last the empty files. Single file groups are left out if
+    /// `omit` is set, and groups within a single root if `cross_root` is set. If there are reference directories,
+    /// only groups holding both reference files and other files are returned.
+    pub fn get_groups(&self) -> Vec<DuplicateGroup> {
+        let mut groups = self.get_all_groups();
+        if self.omit {
+            groups.retain(DuplicateGroup::is_duplicate);
+        }
+        if self.cross_root {
+            groups.retain(DuplicateGroup::spans_roots);
+        }
+        if !self.reference_paths.is_empty() {
+            groups.retain(DuplicateGroup::has_reference);
+        }
+        groups
+    }
+
+    /// Returns every group found, before `omit`, `cross_root` and `reference_paths` filter them.
+    fn get_all_groups(&self) -> Vec<DuplicateGroup> {
         let checksums = self.get_sorted_checksums();
         let map = self.entries.lock().unwrap();
+        let mut groups: Vec<DuplicateGroup> = checksums
+            .into_iter()
+            .map(|checksum| {
+                let files = map[&checksum].clone();
+                DuplicateGroup::new(Some(checksum), files)
+            })
+            .collect();
+        groups.extend(self.collisions.iter().map(|(checksum, files)| DuplicateGroup::new(Some(checksum.clone()), files.clone())));
+        groups.extend(self.unhashed.iter().map(|file| DuplicateGroup::new(None, vec![file.clone()])));
+        if !self.empty.is_empty() {
+            groups.push(DuplicateGroup::new(None, self.empty.clone()));
+        }
+        groups
+    }
+
+    /// Returns the files and directories that were skipped by `parse`, and why.
+    pub fn get_errors(&self) -> &[DupeLsError] {
+        &self.errors
+    }
+
+    /// Moves the errors collected by `parse` out of `DupeLs`.
+    pub fn take_errors(&mut self) -> Vec<DupeLsError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The files of every group as listed by the text format. If there are reference directories, only the
+    /// files outside of them are listed, each with the first reference file that has the same contents. The
+    /// hard links of a file follow it, unless they are listed on their own, and then the symlinks to it.
+    fn get_text_groups(&self) -> Vec<Vec<TextLine>> {
+        self.get_groups()
+            .iter()
+            .map(|group| {
+                let reference = group.references().next().map(|file| file.path.clone());
+                let mut lines = Vec::new();
+                for file in group.files.iter().filter(|file| !file.reference) {
+                    lines.push(TextLine { path: file.path.clone(), reference: reference.clone(), link: None });
+                    lines.extend(
+                        group
+                            .unlisted_hard_links(file)
+                            .map(|path| TextLine { path: path.clone(), reference: None, link: Some(HARD_LINK_PREFIX) }),
+                    );
+                    lines.extend(
+                        file.aliases
+                            .iter()
+                            .map(|path| TextLine { path: path.clone(), reference: None, link: Some(ALIAS_PREFIX) }),
+                    );
+                }
+                lines
+            })
+            .collect()
+    }
+
+    /// A line of the text format: a hard link is prefixed with `= ` and a symlink with `-> `, a file is
+    /// followed by a tab and the reference file it duplicates if there is one.
+    fn get_text_line(&self, line: &TextLine) -> String {
+        let mut text = String::new();
+        if let Some(prefix) = line.link {
+            text.push_str(prefix);
+        }
+        text.push_str(&path_to_string(&line.path, self.escape));
+        if let Some(reference) = &line.reference {
+            text.push('\t');
+            text.push_str(&path_to_string(reference, self.escape));
+        }
+        text
+    }
+
+    pub fn get_output_vec(&self) -> Vec<String> {
         let mut lines = Vec::new();
-        let mut first = true;
-        for checksum in checksums {
-            let paths = &map[&checksum];
-            if paths.len() <= 1 && self.omit {
-                continue;
-            }
-            if !first {
+        for (i, group) in self.get_text_groups().iter().enumerate() {
+            if i > 0 {
                 lines.push(self.seperator.clone());
             }
-            first = false;
-            for path in paths {
-                lines.push(path.clone());
-            }
+            lines.extend(group.iter().map(|line| self.get_text_line(line)));
         }
         lines
     }
 
+    /// Follows `find -print0`: every path ends with `\0` and every group ends with an extra `\0`. Only paths
+    /// are written, so symlinks and hard links that aren't listed on their own are left out, and the reference
+    /// file of a file follows it as a path of its own.
+    fn get_null_terminated_output(&self) -> String {
+        let mut output = String::new();
+        for group in self.get_text_groups() {
+            for line in group.iter().filter(|line| line.link.is_none()) {
+                output.push_str(&path_to_string(&line.path, self.escape));
+                output.push('\0');
+                if let Some(reference) = &line.reference {
+                    output.push_str(&path_to_string(reference, self.escape));
+                    output.push('\0');
+                }
+            }
+            output.push('\0');
+        }
+        output
+    }
+
+    /// The text format with every path written as its raw bytes, so names that aren't valid UTF-8 can
+    /// be passed on to other tools unchanged.
+    fn get_raw_text_output(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        for (i, group) in self.get_text_groups().iter().enumerate() {
+            if i > 0 && !self.null_terminated {
+                output.extend_from_slice(self.seperator.as_bytes());
+                output.push(b'\n');
+            }
+            for line in group {
+                if let Some(prefix) = line.link {
+                    if self.null_terminated {
+                        continue;
+                    }
+                    output.extend_from_slice(prefix.as_bytes());
+                }
+                let terminator = if self.null_terminated { b'\0' } else { b'\n' };
+                output.extend_from_slice(&path_to_bytes(&line.path));
+                if let Some(reference) = &line.reference {
+                    // A tab could be part of a name, so -0 gives the reference a field of its own.
+                    output.push(if self.null_terminated { b'\0' } else { b'\t' });
+                    output.extend_from_slice(&path_to_bytes(reference));
+                }
+                output.push(terminator);
+            }
+            if self.null_terminated {
+                output.push(b'\0');
+            }
+        }
+        if !self.null_terminated {
+            output.pop(); // Match get_output_string, which doesn't end with a newline.
+        }
+        output
+    }
+
+    /// Returns the output as a string. Paths that aren't valid UTF-8 are escaped, use `get_output_bytes`
+    /// to get them unchanged.
     pub fn get_output_string(&self) -> String {
-        let lines = self.get_output_vec();
-        lines.join("\n")
+        if let Some(changes) = &self.changes {
+            return match self.format {
+                OutputFormat::Text => output::changes_to_text(changes, &self.seperator, self.escape),
+                OutputFormat::Json => output::changes_to_json(changes, self.escape),
+                OutputFormat::Ndjson => output::changes_to_ndjson(changes, self.escape),
+                OutputFormat::Csv => output::changes_to_csv(changes, b',', self.escape),
+                OutputFormat::Tsv => output::changes_to_csv(changes, b'\t', self.escape),
+            };
+        }
+        match self.format {
+            OutputFormat::Text if self.null_terminated => self.get_null_terminated_output(),
+            OutputFormat::Text => self.get_output_vec().join("\n"),
+            OutputFormat::Json => output::to_json(&self.get_groups(), self.escape),
+            OutputFormat::Ndjson => output::to_ndjson(&self.get_groups(), self.escape),
+            OutputFormat::Csv => output::to_csv(&self.get_groups(), b',', self.escape),
+            OutputFormat::Tsv => output::to_csv(&self.get_groups(), b'\t', self.escape),
+        }
+    }
+
+    /// Returns the output as bytes. Unless `escape` is set, the text format writes every path as it is
+    /// stored by the OS.
+    pub fn get_output_bytes(&self) -> Vec<u8> {
+        match (self.format, &self.changes) {
+            (OutputFormat::Text, Some(changes)) if !self.escape => output::changes_to_raw_text(changes, &self.seperator),
+            (OutputFormat::Text, None) if !self.escape => self.get_raw_text_output(),
+            _ => self.get_output_string().into_bytes(),
+        }
     }
 
     pub fn parse(&mut self) {
-        let (s, r) = unbounded::<String>();
-        let entries = Arc::clone(&self.entries);
+        let (s, r) = unbounded::<FileEntry>();
+        let roots = self.get_roots().enumerate().map(|(root, base_path)| {
+            let root_id = fs::metadata(base_path).ok().and_then(|metadata| FileEntry::file_id(&metadata));
+            let state = WalkState {
+                root,
+                reference: root >= self.base_paths.len(),
+                device: root_id.filter(|_| self.one_file_system).map(|(device, _)| device),
+                ancestors: root_id.into_iter().collect(),
+                ignores: Vec::new(),
+                parent_ignores: Arc::new(if self.respect_ignore {
+                    DupeLs::read_parent_ignore_files(base_path)
+                } else {
+                    Vec::new()
+                }),
+            };
+            DirJob { path: base_path.clone(), depth: self.depth, linked: false, state }
+        });
+        self.walk_in_parallel(roots.collect(), &s);
+        drop(s);
+
+        // Only files sharing their size with another file can be duplicates.
+        let mut sizes: HashMap<u64, Vec<FileEntry>> = HashMap::new();
+        let has_references = !self.reference_paths.is_empty();
+        // A manifest needs the checksum of every file, unique or not. Files from manifests have no known size,
+        // so every file could be a duplicate of one of them.
+        let hash_all = self.manifest.is_some() || !self.from_manifests.is_empty();
+        // A snapshot records the groups hidden by the reference directories as well.
+        let snapshot = self.save_snapshot.is_some() || self.since_snapshot.is_some();
+        for file in self.merge_links(r.iter().collect()) {
+            if file.size == 0 {
+                // Empty files are only sent if they are listed, they are all the same so don't need hashing.
+                self.empty.push(file);
+                continue;
+            }
+            if file.symlink && self.symlinks == SymlinkPolicy::List {
+                // Listed links are never read, so they can't be compared.
+                self.unhashed.push(file);
+                continue;
+            }
+            sizes.entry(file.size).or_default().push(file);
+        }
+        if has_references && !hash_all && !snapshot {
+            // Files can only be reported next to a reference file of the same size.
+            sizes.retain(|_, files| {
+                files.iter().any(|file| file.reference) && files.iter().any(|file| !file.reference)
+            });
+        }
+        let candidates = if hash_all { sizes.into_values().flatten().collect() } else { self.keep_collisions(sizes) };
+        // Unchanged files get their checksum from the cache, they don't need to be sampled either.
+        let (cached, candidates) = self.take_cached(candidates);
+        // A file the size of a cached file may be its duplicate even if it's the only one left to sample, so it
+        // is hashed in full to compare it with the cached checksums.
+        let cached_sizes: HashSet<u64> = cached.iter().map(|(file, _)| file.size).collect();
+
+        // Files larger than the head and tail sample are sampled first, the rest are hashed in full right away.
+        let (to_sample, mut to_hash): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|file| {
+            self.sample_size > 0
+                && !hash_all
+                && file.size > self.sample_size.saturating_mul(2)
+                && !cached_sizes.contains(&file.size)
+        });
+        let mut samples: HashMap<(u64, Digest), Vec<FileEntry>> = HashMap::new();
+        for (file, checksum) in self.checksum_in_parallel(to_sample, Some(self.sample_size)) {
+            samples.entry((file.size, checksum)).or_default().push(file);
+        }
+        to_hash.append(&mut self.keep_collisions(samples));
+
+        let checksums = self.checksum_in_parallel(to_hash, None);
+        if let (Some(cache), Some(cache_dir)) = (&self.cache, &self.cache_dir)
+            && let Err(e) = cache.flush()
+        {
+            let _ = self.error_s.send(DupeLsError::Cache { path: cache_dir.clone(), source: e });
+        }
+        let mut map = self.entries.lock().unwrap();
+        for (file, checksum) in cached.into_iter().chain(checksums) {
+            map.entry(checksum).or_default().push(file);
+        }
+        drop(map);
+
+        if self.verify {
+            self.verify_entries();
+        }
+        // Only the files found by the walk go in the manifest written, so it is written before others are read.
+        if let Some(path) = &self.manifest
+            && let Err(e) = self.write_manifest(path)
+        {
+            let _ = self.error_s.send(DupeLsError::Manifest { path: path.clone(), source: e });
+        }
+        // Files from manifests can't be verified, they join the group that kept their checksum.
+        self.read_manifests();
+
+        self.unhashed.sort_by(|a, b| a.path.cmp(&b.path));
+        if !self.omit {
+            // Unique files are still listed, make sure they can at least be opened.
+            let error_s = &self.error_s;
+            self.unhashed.retain(|file| match fs::File::open(&file.path) {
+                Ok(_) => true,
+                Err(e) => {
+                    let _ = error_s.send(DupeLsError::file(&file.path, e));
+                    false
+                }
+            });
+        }
+
+        if self.save_snapshot.is_some() || self.since_snapshot.is_some() {
+            self.compare_snapshots();
+        }
+
+        self.errors.extend(self.error_r.try_iter());
+        self.errors.sort_by(|a, b| a.path().cmp(b.path()));
+    }
+
+    /// Adds the files listed in `from_manifests` to `entries`. They get the size of the files found by the walk
+    /// with the same checksum, or 0 if there are none. Paths that were found by the walk or are listed by an
+    /// earlier manifest are skipped, so a file is never its own duplicate.
+    fn read_manifests(&mut self) {
+        let first_root = self.get_roots().count();
+        let empty_checksum = self.hash.hasher().finish();
+        let mut map = self.entries.lock().unwrap();
+        let mut found: HashSet<PathBuf> = map
+            .values()
+            .chain(self.collisions.iter().map(|(_, files)| files))
+            .flatten()
+            .chain(&self.unhashed)
+            .chain(&self.empty)
+            .flat_map(|file| std::iter::once(&file.path).chain(&file.hard_links).chain(&file.aliases))
+            .cloned()
+            .collect();
+        for (i, path) in self.from_manifests.iter().enumerate() {
+            let (files, improper) = match manifest::read(path, self.hash) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    let _ = self.error_s.send(DupeLsError::Manifest { path: path.clone(), source: e });
+                    continue;
+                }
+            };
+            if improper > 0 {
+                let message = format!("{} lines are improperly formatted or not {} checksums", improper, self.hash);
+                let source = io::Error::new(io::ErrorKind::InvalidData, message);
+                let _ = self.error_s.send(DupeLsError::Manifest { path: path.clone(), source });
+            }
+            for (file_path, checksum) in files {
+                if !found.insert(file_path.clone()) {
+                    continue;
+                }
+                let file = FileEntry {
+                    path: file_path,
+                    size: map.get(&checksum).and_then(|files| files.first()).map_or(0, |file| file.size),
+                    modified: None,
+                    hard_links: Vec::new(),
+                    aliases: Vec::new(),
+                    symlink: false,
+                    root: first_root + i,
+                    reference: false,
+                    file_id: None,
+                };
+                if checksum != empty_checksum {
+                    map.entry(checksum).or_default().push(file);
+                } else if self.empty_files == EmptyFilePolicy::List {
+                    self.empty.push(file);
+                }
+            }
+        }
+    }
+
+    /// Writes the checksum of every hashed file and its hard links to `path`. Empty files are never read, they
+    /// get the checksum of no data.
+    fn write_manifest(&self, path: &Path) -> io::Result<()> {
+        let entries = self.entries.lock().unwrap();
+        let hashed = entries.iter().chain(self.collisions.iter().map(|(checksum, files)| (checksum, files)));
+        let empty_checksum = self.hash.hasher().finish();
+        let empty = std::iter::once((&empty_checksum, &self.empty));
+        let mut files: Vec<(&Path, &Digest)> = hashed
+            .chain(empty)
+            .flat_map(|(checksum, files)| files.iter().map(move |file| (file, checksum)))
+            .flat_map(|(file, checksum)| {
+                std::iter::once(&file.path).chain(&file.hard_links).map(move |path| (path.as_path(), checksum))
+            })
+            .collect();
+        manifest::write(path, &mut files)
+    }
+
+    /// Compares the groups found against `since_snapshot`, then saves them to `save_snapshot`. Both can be the
+    /// same file to report the changes since the last run.
+    ///
+    /// The snapshot holds every group found by the walk, whatever the output filters, but not the files read
+    /// from manifests. Unique files that were in the older snapshot are hashed, so a file left alone in its
+    /// group is still matched to it.
+    fn compare_snapshots(&mut self) {
+        let older = self.since_snapshot.as_ref().map(|path| {
+            Snapshot::load(path).and_then(|older| {
+                if older.hash == self.hash.name() {
+                    Ok(older)
+                } else {
+                    let message = format!("it was made with --hash {}, not {}", older.hash, self.hash.name());
+                    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+                }
+            })
+        });
+        let first_manifest_root = self.get_roots().count();
+        let mut groups = self.get_all_groups();
+        for group in &mut groups {
+            group.files.retain(|file| file.root < first_manifest_root);
+        }
+        groups.retain(|group| !group.files.is_empty());
+        if let Some(Ok(older)) = &older {
+            let paths = older.paths();
+            for file in self.unhashed.iter().filter(|file| paths.contains(file.path.as_path())) {
+                match DupeLs::get_checksum(&file.path, self.hash) {
+                    Ok(checksum) => groups.push(DuplicateGroup::new(Some(checksum), vec![file.clone()])),
+                    Err(e) => {
+                        let _ = self.error_s.send(e);
+                    }
+                }
+            }
+        }
+        let snapshot = Snapshot::new(&groups, self.hash);
+        if let (Some(path), Some(older)) = (&self.since_snapshot, older) {
+            match older {
+                Ok(older) => self.changes = Some(snapshot.changes_since(&older)),
+                Err(e) => {
+                    let _ = self.error_s.send(DupeLsError::Snapshot { path: path.clone(), source: e });
+                }
+            }
+        }
+        if let Some(path) = &self.save_snapshot
+            && let Err(e) = snapshot.save(path)
+        {
+            let _ = self.error_s.send(DupeLsError::Snapshot { path: path.clone(), source: e });
+        }
+    }
+
+    /// Merges every set of paths that lead to the same inode. Symlinks become `aliases` of the first real path,
+    /// or of the first symlink if the file itself wasn't found. Hard links are listed in `hard_links`, and unless
+    /// `count_hard_links` is set only the link with the lowest path is kept so the file is hashed and listed once.
+    fn merge_links(&self, files: Vec<FileEntry>) -> Vec<FileEntry> {
+        let mut inodes: HashMap<(u64, u64), Vec<FileEntry>> = HashMap::new();
+        let mut merged = Vec::new();
+        for file in files {
+            match file.file_id {
+                Some(file_id) => inodes.entry(file_id).or_default().push(file),
+                None => merged.push(file),
+            }
+        }
+        for (_, files) in inodes {
+            let (mut links, mut aliases): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| !file.symlink);
+            // Roots that overlap find the same paths more than once, the first root keeps them.
+            links.sort_by(|a, b| a.path.cmp(&b.path).then(a.root.cmp(&b.root)));
+            links.dedup_by(|a, b| a.path == b.path);
+            aliases.sort_by(|a, b| a.path.cmp(&b.path).then(a.root.cmp(&b.root)));
+            aliases.dedup_by(|a, b| a.path == b.path);
+            if links.is_empty() {
+                links.push(aliases.remove(0));
+            }
+            let paths: Vec<PathBuf> = links.iter().map(|file| file.path.clone()).collect();
+            if !self.count_hard_links {
+                links.truncate(1);
+            }
+            for (i, mut file) in links.into_iter().enumerate() {
+                file.hard_links = paths.iter().filter(|path| **path != file.path).cloned().collect();
+                if i == 0 {
+                    file.aliases = aliases.iter().map(|alias| alias.path.clone()).collect();
+                }
+                merged.push(file);
+            }
+        }
+        merged
+    }
+
+    /// Splits `files` into the ones with a checksum in the cache, and the ones that still need to be hashed.
+    fn take_cached(&self, files: Vec<FileEntry>) -> (Vec<(FileEntry, Digest)>, Vec<FileEntry>) {
+        let Some(cache) = &self.cache else {
+            return (Vec::new(), files);
+        };
+        let mut cached = Vec::new();
+        let mut uncached = Vec::new();
+        for file in files {
+            match cache.get(&file, self.hash) {
+                Some(checksum) => cached.push((file, checksum)),
+                None => uncached.push(file),
+            }
+        }
+        (cached, uncached)
+    }
+
+    /// Returns the files of every group with more than one member, single files are moved to `unhashed`.
+    fn keep_collisions<K: Eq + Hash>(&mut self, groups: HashMap<K, Vec<FileEntry>>) -> Vec<FileEntry> {
+        let mut collisions = Vec::new();
+        for (_, mut files) in groups {
+            if files.len() > 1 {
+                collisions.append(&mut files);
+            } else {
+                self.unhashed.append(&mut files);
+            }
+        }
+        collisions
+    }
+
+    /// Splits every entry into groups of files whose contents are identical byte-for-byte.
+    /// The group holding the first file keeps the checksum, any others are moved to `collisions`.
+    fn verify_entries(&mut self) {
+        let groups: Vec<(Digest, Vec<FileEntry>)> = self.entries.lock().unwrap()
+            .iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(checksum, files)| (checksum.clone(), files.clone()))
+            .collect();
+        let error_s = self.error_s.clone();
+        let verified = self.in_parallel(groups, move |(checksum, files)| {
+            Some((checksum, DupeLs::split_by_contents(files, &error_s)))
+        });
+        let mut map = self.entries.lock().unwrap();
+        for (checksum, mut split) in verified {
+            if split.is_empty() {
+                map.remove(&checksum);
+                continue;
+            }
+            map.insert(checksum.clone(), split.remove(0));
+            self.collisions.extend(split.into_iter().map(|files| (checksum.clone(), files)));
+        }
+    }
+
+    /// Computes checksums over `max_threads` worker threads. If `sample` is set, only the head and tail
+    /// of each file is hashed. Hard links to the same file are only read once. Files that can't be read
+    /// are reported and left out of the result.
+    fn checksum_in_parallel(&self, files: Vec<FileEntry>, sample: Option<u64>) -> Vec<(FileEntry, Digest)> {
+        let mut batches: Vec<Vec<FileEntry>> = Vec::new();
+        let mut inodes: HashMap<(u64, u64), usize> = HashMap::new();
+        for file in files {
+            match file.file_id.and_then(|file_id| inodes.get(&file_id)) {
+                Some(&i) => batches[i].push(file),
+                None => {
+                    if let Some(file_id) = file.file_id {
+                        inodes.insert(file_id, batches.len());
+                    }
+                    batches.push(vec![file]);
+                }
+            }
+        }
+
+        let hash = self.hash;
+        let error_s = self.error_s.clone();
+        let cache = self.cache.clone();
+        self.in_parallel(batches, move |batch| {
+            let file = &batch[0];
+            let checksum = match sample {
+                Some(sample_size) => DupeLs::get_sample_checksum(&file.path, file.size, sample_size, hash),
+                None => DupeLs::get_checksum(&file.path, hash),
+            };
+            match checksum {
+                Ok(checksum) => {
+                    if let Some(cache) = cache.as_ref().filter(|_| sample.is_none()) {
+                        for file in &batch {
+                            cache.insert(file, hash, &checksum);
+                        }
+                    }
+                    Some(batch.into_iter().map(|file| (file, checksum.clone())).collect::<Vec<_>>())
+                }
+                Err(err) => {
+                    let _ = error_s.send(err);
+                    None
+                }
+            }
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Applies `f` to every item over `max_threads` worker threads, collecting the results that are `Some`.
+    fn in_parallel<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Clone + Send + 'static,
+    {
+        let (s, r) = unbounded::<T>();
+        let (result_s, result_r) = unbounded::<R>();
 
         let mut handles: Vec<thread::JoinHandle<()>> = Vec::new();
         for _ in 0..self.max_threads {
             let r_thread = r.clone();  // Thread will own a receiver pointing to the same channel.
-            let entries = Arc::clone(&entries);  // Each thread will have its own reference to the entries map.
+            let result_s = result_s.clone();
+            let f = f.clone();
             handles.push(thread::spawn(move || {
-                for path in r_thread.iter() {
-                    match DupeLs::get_checksum(&path) {
-                        Ok(checksum) => {
-                            let mut map = entries.lock().unwrap();
-                            map.entry(checksum).or_insert_with(Vec::new).push(path);        
-                        }
-                        Err(err_msg) => {
-                            eprintln!("{}", err_msg);
-                        }
+                for item in r_thread.iter() {
+                    if let Some(result) = f(item) {
+                        let _ = result_s.send(result);
                     }
                 }
             }));
         }
 
-        self.walk_and_send(&self.base_path, self.depth, &s);
+        for item in items {
+            let _ = s.send(item);
+        }
 
         drop(s);
+        drop(result_s);
 
         for handle in handles {
             let _ = handle.join();
         }
+        result_r.iter().collect()
+    }
+
+    /// Walks the `roots` over `walk_threads` threads and sends every file found to `s`. Each thread takes
+    /// subdirectories from its own queue first, and steals from the other threads once it runs out.
+    fn walk_in_parallel(&self, roots: Vec<DirJob>, s: &Sender<FileEntry>) {
+        let workers: Vec<Worker<DirJob>> = (0..self.walk_threads).map(|_| Worker::new_lifo()).collect();
+        let queue = WalkQueue {
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            pending: AtomicUsize::new(roots.len()),
+            lock: Mutex::new(()),
+            idle: Condvar::new(),
+        };
+        for job in roots {
+            queue.injector.push(job);
+        }
+        thread::scope(|scope| {
+            for worker in workers {
+                let queue = &queue;
+                scope.spawn(move || {
+                    loop {
+                        match queue.find_job(&worker) {
+                            Some(job) => {
+                                let _walking = Walking(queue);
+                                self.walk_and_send(job, &worker, queue, s);
+                            }
+                            None if !queue.wait() => break,
+                            None => {}
+                        }
+                    }
+                });
+            }
+        });
     }
 
-    fn walk_and_send(&self, dir_path: &Path, depth: usize, s: &Sender<String>) {
+    /// Sends every file in the directory of `job` to `s`, and queues its subdirectories on `queue`.
+    fn walk_and_send(&self, job: DirJob, local: &Worker<DirJob>, queue: &WalkQueue, s: &Sender<FileEntry>) {
+        let DirJob { path: dir_path, depth, linked, mut state } = job;
         if depth == 0 || !dir_path.is_dir() {
             return;
         }
-        let entries = match fs::read_dir(dir_path) {
+        let entries = match fs::read_dir(&dir_path) {
             Ok(entries) => entries,
-            Err(_) => return,
+            Err(e) => {
+                let _ = self.error_s.send(DupeLsError::dir(&dir_path, e));
+                return;
+            }
         };
-        for entry in entries.filter_map(Result::ok) {
+        if self.respect_ignore {
+            state.ignores.extend(DupeLs::read_ignore_files(&dir_path).map(Arc::new));
+        }
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let _ = self.error_s.send(DupeLsError::dir(&dir_path, e));
+                    continue;
+                }
+            };
             let path = entry.path();
-            if let Ok(metadata) = entry.metadata() {
-                if metadata.is_dir() {
-                    if self.recursive {
-                        self.walk_and_send(&path, depth - 1, s);
+            let mut metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    let _ = self.error_s.send(DupeLsError::file(&path, e));
+                    continue;
+                }
+            };
+            let is_symlink = metadata.is_symlink();
+            if is_symlink {
+                if self.symlinks == SymlinkPolicy::Ignore {
+                    continue;
+                }
+                metadata = match fs::metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        let _ = self.error_s.send(DupeLsError::file(&path, e));
+                        continue;
                     }
-                } else {
-                    if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
-                        if self.is_dot_file(filename) && !self.track_dot_files {
+                };
+            }
+            let relative_path = self
+                .get_roots()
+                .nth(state.root)
+                .and_then(|root| path.strip_prefix(root).ok())
+                .unwrap_or(&path);
+            if self.respect_ignore && self.is_ignored(&state, &path, relative_path, metadata.is_dir()) {
+                continue;
+            }
+            if metadata.is_dir() {
+                if self.recursive
+                    && !(is_symlink && self.symlinks == SymlinkPolicy::List)
+                    && self.filter.walk_dir(relative_path)
+                {
+                    let dir_id = FileEntry::file_id(&metadata);
+                    if dir_id.is_some_and(|dir_id| state.ancestors.contains(&dir_id)) {
+                        continue; // A symlink back to a parent directory.
+                    }
+                    if let (Some(device), Some((dir_device, _))) = (state.device, dir_id)
+                        && dir_device != device
+                    {
+                        continue; // A mount point.
+                    }
+                    let mut state = state.clone();
+                    state.ancestors.extend(dir_id);
+                    queue.push(local, DirJob { path, depth: depth - 1, linked: linked || is_symlink, state });
+                }
+            } else {
+                if let Some(filename) = path.file_name()
+                    && self.is_dot_file(filename)
+                    && !self.track_dot_files
+                {
+                    continue;
+                }
+                if !self.filter.keep_file(relative_path) {
+                    continue;
+                }
+                if !metadata.is_file() {
+                    // Reading a FIFO or device could block forever, only regular files are hashed.
+                    let kind = DupeLs::special_file_kind(&metadata.file_type());
+                    let _ = self.error_s.send(DupeLsError::NotRegular { path, kind });
+                    continue;
+                }
+                let size = metadata.len();
+                if size < self.min_size || self.max_size.is_some_and(|max_size| size > max_size) {
+                    continue;
+                }
+                if size == 0 && self.empty_files == EmptyFilePolicy::Skip {
+                    continue;
+                }
+                if !self.types.is_empty() {
+                    match FileType::matches(&self.types, &path) {
+                        Ok(true) => {}
+                        Ok(false) => continue,
+                        Err(e) => {
+                            let _ = self.error_s.send(DupeLsError::file(&path, e));
                             continue;
                         }
                     }
-                    let _ = s.send(path.to_string_lossy().to_string());
                 }
+                let mut file = FileEntry::new(path, &metadata);
+                file.symlink = linked || is_symlink;
+                file.root = state.root;
+                file.reference = state.reference;
+                let _ = s.send(file);
+            }
+        }
+    }
+
+    /// Reads the `.gitignore`, `.ignore` and `.git/info/exclude` files of a directory. Lines that can't be
+    /// parsed are skipped, like git does.
+    fn read_ignore_files(dir_path: &Path) -> Option<Gitignore> {
+        let mut builder = GitignoreBuilder::new(dir_path);
+        let mut found = false;
+        for name in [".gitignore", ".ignore", ".git/info/exclude"] {
+            let path = dir_path.join(name);
+            if path.is_file() {
+                found = true;
+                let _ = builder.add(path);
+            }
+        }
+        if !found {
+            return None;
+        }
+        builder.build().ok()
+    }
+
+    /// Reads the ignore files of the directories above `root`, like git does for a subdirectory of a repository.
+    /// Stops at the root of the git repository, and returns none if `root` isn't inside of one.
+    fn read_parent_ignore_files(root: &Path) -> Vec<(PathBuf, Gitignore)> {
+        let Ok(root) = fs::canonicalize(root) else {
+            return Vec::new();
+        };
+        let mut ignores = Vec::new();
+        for dir in root.ancestors() {
+            if dir != root
+                && let Some(ignore) = DupeLs::read_ignore_files(dir)
+                && let Ok(relative_root) = root.strip_prefix(dir)
+            {
+                ignores.push((relative_root.to_path_buf(), ignore));
+            }
+            if dir.join(".git").exists() {
+                ignores.reverse();
+                return ignores;
+            }
+        }
+        Vec::new()
+    }
+
+    /// Checks `path` against the ignore files of its directory and every parent directory, the closest file
+    /// that has a matching rule decides. The `.git` directory itself is always ignored. `relative_path` is the
+    /// path relative to the root directory, used for the ignore files above it.
+    fn is_ignored(&self, state: &WalkState, path: &Path, relative_path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name() == Some(OsStr::new(".git")) {
+            return true;
+        }
+        for ignore in state.ignores.iter().rev() {
+            match ignore.matched(path, is_dir) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
+            }
+        }
+        for (relative_root, ignore) in state.parent_ignores.iter().rev() {
+            match ignore.matched(relative_root.join(relative_path), is_dir) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
             }
         }
+        self.global_ignore.matched(path, is_dir).is_ignore()
+    }
+
+    /// Names the type of a file that is neither a regular file nor a directory.
+    #[cfg(unix)]
+    fn special_file_kind(file_type: &fs::FileType) -> &'static str {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            "FIFO"
+        } else if file_type.is_socket() {
+            "socket"
+        } else if file_type.is_block_device() {
+            "block device"
+        } else if file_type.is_char_device() {
+            "character device"
+        } else {
+            "special file"
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn special_file_kind(_file_type: &fs::FileType) -> &'static str {
+        "special file"
     }
 
-    fn is_dot_file(&self, filename: &str) -> bool {
-        filename.starts_with('.')
+    fn is_dot_file(&self, filename: &OsStr) -> bool {
+        filename.as_encoded_bytes().starts_with(b".")
     }
 
-    fn get_checksum(path: &str) -> Result<md5::Digest, String> {
+    fn get_checksum(path: &Path, algorithm: ChecksumAlgorithm) -> Result<Digest, DupeLsError> {
         let mut file = fs::File::open(path)
-            .map_err(|e| format!("Could not open file '{}': {}", path, e))?;
-        let mut context = md5::Context::new();
+            .map_err(|e| DupeLsError::file(path, e))?;
+        let mut hasher = algorithm.hasher();
         let mut buffer = [0u8; CHECKSUM_READ_BUFFER_SIZE];
         loop {
             let bytes_read = file.read(&mut buffer)
-                .map_err(|e| format!("Error reading file '{}': {}", path, e))?;
+                .map_err(|e| DupeLsError::file(path, e))?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Partitions `files` into groups with identical contents. Each file is streamed against the
+    /// first file of every group found so far. Files that can't be read are reported and dropped. If the
+    /// first file of a group can't be read any more, the other files of its group are placed again.
+    fn split_by_contents(files: Vec<FileEntry>, errors: &Sender<DupeLsError>) -> Vec<Vec<FileEntry>> {
+        let mut groups: Vec<Vec<FileEntry>> = Vec::new();
+        let mut pending: VecDeque<FileEntry> = files.into();
+        'files: while let Some(file) = pending.pop_front() {
+            for i in 0..groups.len() {
+                let group = &mut groups[i];
+                if file.file_id.is_some() && file.file_id == group[0].file_id {
+                    group.push(file);
+                    continue 'files;
+                }
+                match DupeLs::files_equal(&group[0].path, &file.path) {
+                    Ok(true) => {
+                        group.push(file);
+                        continue 'files;
+                    }
+                    Ok(false) => {}
+                    Err(err) if err.path() == group[0].path => {
+                        let _ = errors.send(err);
+                        let mut group = groups.remove(i);
+                        group.remove(0);
+                        pending.push_front(file);
+                        for file in group.into_iter().rev() {
+                            pending.push_front(file);
+                        }
+                        continue 'files;
+                    }
+                    Err(err) => {
+                        let _ = errors.send(err);
+                        continue 'files;
+                    }
+                }
+            }
+            groups.push(vec![file]);
+        }
+        groups
+    }
+
+    fn files_equal(a: &Path, b: &Path) -> Result<bool, DupeLsError> {
+        let mut file_a = fs::File::open(a)
+            .map_err(|e| DupeLsError::file(a, e))?;
+        let mut file_b = fs::File::open(b)
+            .map_err(|e| DupeLsError::file(b, e))?;
+        let mut buffer_a = [0u8; CHECKSUM_READ_BUFFER_SIZE];
+        let mut buffer_b = [0u8; CHECKSUM_READ_BUFFER_SIZE];
+        loop {
+            let read_a = DupeLs::read_full(&mut file_a, &mut buffer_a)
+                .map_err(|e| DupeLsError::file(a, e))?;
+            let read_b = DupeLs::read_full(&mut file_b, &mut buffer_b)
+                .map_err(|e| DupeLsError::file(b, e))?;
+            if buffer_a[..read_a] != buffer_b[..read_b] {
+                return Ok(false);
+            }
+            if read_a == 0 {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Reads until `buffer` is full or the end of the file is reached.
+    fn read_full(file: &mut fs::File, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut total = 0;
+        while total < buffer.len() {
+            let bytes_read = file.read(&mut buffer[total..])?;
             if bytes_read == 0 {
                 break;
             }
-            context.consume(&buffer[..bytes_read]);
+            total += bytes_read;
         }
-        Ok(context.compute())
+        Ok(total)
+    }
+
+    /// Hashes the first and last `sample_size` bytes of a file of `size` bytes.
+    fn get_sample_checksum(path: &Path, size: u64, sample_size: u64, algorithm: ChecksumAlgorithm) -> Result<Digest, DupeLsError> {
+        let mut file = fs::File::open(path)
+            .map_err(|e| DupeLsError::file(path, e))?;
+        let mut sample = Vec::new();
+        (&mut file).take(sample_size).read_to_end(&mut sample)
+            .map_err(|e| DupeLsError::file(path, e))?;
+        file.seek(SeekFrom::Start(size.saturating_sub(sample_size).max(sample_size)))
+            .and_then(|_| file.take(sample_size).read_to_end(&mut sample))
+            .map_err(|e| DupeLsError::file(path, e))?;
+        let mut hasher = algorithm.hasher();
+        hasher.update(&sample);
+        Ok(hasher.finish())
     }
 }
 
@@ -217,6 +1423,7 @@ impl DupeLs {
 mod test {
 
     use super::*;
+    use crate::filter::parse_glob;
     use std::fs::{self, File};
     use std::io::Write;
     use tempfile::tempdir;
@@ -251,6 +1458,11 @@ mod test {
         file_path
     }
 
+    fn file_entry(path: &std::path::Path) -> FileEntry {
+        FileEntry::new(path.to_path_buf(), &fs::metadata(path).unwrap())
+    }
+
+    #[allow(clippy::vec_init_then_push)]
     fn setup_test_files() -> (tempfile::TempDir, Vec<std::path::PathBuf>) {
         let dir = tempdir().unwrap();
         let mut files = Vec::new();
@@ -277,21 +1489,21 @@ mod test {
     #[test]
     fn init_test_with_no_file_no_r_flag() {
         let config = DupeLsConfig {
-            base_path: None,
+            base_paths: Vec::new(),
             track_dot_files: true,
             recursive: false,
             depth: 2,
             omit: false,
             max_threads: Some(1),
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.track_dot_files);
         assert!(!d.recursive);
         assert_eq!(d.depth, 3);
         assert!(!d.omit);
-        assert!(d.base_path.is_dir());
-        assert_eq!(d.base_path, env::current_dir().unwrap());
+        assert_eq!(d.base_paths, vec![env::current_dir().unwrap()]);
         assert_eq!("---".to_string(), d.seperator);
     }
 
@@ -299,22 +1511,21 @@ mod test {
     fn init_test_with_r_flag() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 2,
             omit: false,
             max_threads: Some(1),
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.track_dot_files);
         assert!(d.recursive);
         assert_eq!(d.depth, 3);
         assert!(!d.omit);
-        assert!(d.base_path.is_dir());
-        assert!(d.base_path.exists());
-        assert_eq!(d.base_path, dir.path().to_path_buf());
+        assert_eq!(d.base_paths, vec![dir.path().to_path_buf()]);
         assert_eq!("---".to_string(), d.seperator);
     }
 
@@ -322,13 +1533,14 @@ mod test {
     fn init_test_with_rd_flags() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 3,
             omit: false,
             max_threads: Some(1),
             seperator: "hi".to_string(),
+            ..Default::default()
         };
         let f = DupeLs::new(config);
         assert!(f.track_dot_files);
@@ -342,29 +1554,32 @@ mod test {
     fn test_thread_default() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 3,
             omit: false,
             max_threads: None,
             seperator: "hi".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.max_threads <= MAX_THREAD_LIMIT);
     }
 
     #[test]
+    #[allow(clippy::int_plus_one)]
     fn test_legal_specified_max_thread() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 3,
             omit: false,
             max_threads: Some(MAX_THREAD_LIMIT - 1),
             seperator: "hi".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.max_threads <= MAX_THREAD_LIMIT - 1);
@@ -374,13 +1589,14 @@ mod test {
     fn test_thread_safe_guard() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 3,
             omit: false,
             max_threads: Some(num_cpus::get() + 10),
             seperator: "hi".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.max_threads <= MAX_THREAD_LIMIT);
@@ -390,13 +1606,14 @@ mod test {
     fn test_thread_zero_threads() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(PathBuf::from(dir.path().to_path_buf())),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 3,
             omit: false,
             max_threads: Some(0),
             seperator: "hi".to_string(),
+            ..Default::default()
         };
         let d = DupeLs::new(config);
         assert!(d.max_threads <= 1);
@@ -408,7 +1625,7 @@ mod test {
         let file_path = files.iter().find(|p| p.ends_with("1.txt")).unwrap();
         let expected_dupe_md5: &str = "8b1a9953c4611296a827abf8c47804d7";
         assert_eq!(
-            format!("{:x}", DupeLs::get_checksum(file_path.to_str().unwrap()).unwrap()),
+            format!("{:x}", DupeLs::get_checksum(file_path, ChecksumAlgorithm::Md5).unwrap()),
             expected_dupe_md5
         );
     }
@@ -416,16 +1633,18 @@ mod test {
     #[test]
     fn test_get_checksum_bad_path_fail() {
         let invalid_path = "/invalid/path/to/nonexistent/file.txt";
-        let result = DupeLs::get_checksum(invalid_path);
+        let result = DupeLs::get_checksum(Path::new(invalid_path), ChecksumAlgorithm::Md5);
         assert!(result.is_err());
-        let error_message = result.unwrap_err();
-        assert!(error_message.contains(&format!("Could not open file '{}'", invalid_path)));
+        let error = result.unwrap_err();
+        assert!(matches!(error, DupeLsError::Vanished { .. }));
+        assert_eq!(error.path(), Path::new(invalid_path));
+        assert!(error.to_string().contains(&format!("Could not open file '{}'", invalid_path)));
     }
 
     #[test]
     fn test_get_checksum_on_directory() {
         let dir = tempdir().unwrap();
-        let result = super::DupeLs::get_checksum(dir.path().to_str().unwrap());
+        let result = super::DupeLs::get_checksum(dir.path(), ChecksumAlgorithm::Md5);
         assert!(result.is_err());
     }
 
@@ -433,84 +1652,89 @@ mod test {
     fn test_parse_no_r_1_thread() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 1,
             omit: false,
             max_threads: Some(1),
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
         let map = d.entries.lock().unwrap();
-        assert_eq!(map.len(), 3);
+        assert_eq!(map.len() + d.unhashed.len(), 3);
     }
 
     #[test]
     fn test_parse_no_r_default_threads() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 1,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
         let map = d.entries.lock().unwrap();
-        assert_eq!(map.len(), 3);
+        assert_eq!(map.len() + d.unhashed.len(), 3);
     }
 
     #[test]
     fn test_parse_r_d_2_1_thread() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 1,
             omit: false,
             max_threads: Some(1),
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
         let map = d.entries.lock().unwrap();
-        assert_eq!(map.len(), 5);
+        assert_eq!(map.len() + d.unhashed.len(), 5);
     }
 
     #[test]
     fn test_parse_r_d_2_default_threads() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 1,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
         let map = d.entries.lock().unwrap();
-        assert_eq!(map.len(), 5);
+        assert_eq!(map.len() + d.unhashed.len(), 5);
     }
     #[test]
     fn test_get_sorted_checksums() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: true,
             depth: 1,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
 
         let mut d = DupeLs::new(config);
@@ -535,13 +1759,14 @@ mod test {
     fn test_get_output_vec() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 0,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
 
         let mut d = DupeLs::new(config);
@@ -557,13 +1782,14 @@ mod test {
     fn test_get_output_str() {
         let (dir, _files) = setup_test_files();
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 0,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
 
         let mut d = DupeLs::new(config);
@@ -577,18 +1803,284 @@ mod test {
     }
 
     #[test]
-    #[cfg(unix)]
-    fn test_parse_dir_with_permission_denied_file() {
+    fn test_parse_skips_hashing_unique_sizes() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            track_dot_files: true,
+            recursive: false,
+            max_threads: Some(1),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let map = d.entries.lock().unwrap();
+
+        // Only 1.txt and 2.txt share a size, so only they are hashed.
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.values().next().unwrap().len(), 2);
+        assert_eq!(d.unhashed.len(), 2);
+        assert!(d.unhashed.iter().any(|p| p.path.ends_with("3.txt")));
+        assert!(d.unhashed.iter().any(|p| p.path.ends_with(".env.test")));
+    }
+
+    #[test]
+    fn test_parse_same_size_different_content() {
+        let dir = tempdir().unwrap();
+        create_test_file(dir.path(), "a.txt", "Hello");
+        create_test_file(dir.path(), "b.txt", "World");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            max_threads: Some(1),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let map = d.entries.lock().unwrap();
+        assert_eq!(map.len(), 2);
+        assert!(d.unhashed.is_empty());
+    }
+
+    fn create_sized_test_file(dir: &std::path::Path, name: &str, size: usize, middle: u8) -> std::path::PathBuf {
+        let mut contents = vec![b'a'; size];
+        contents[size / 2] = middle;
+        let file_path = dir.join(name);
+        fs::write(&file_path, contents).unwrap();
+        file_path
+    }
+
+    #[test]
+    fn test_get_sample_checksum_ignores_middle() {
+        let dir = tempdir().unwrap();
+        let a = create_sized_test_file(dir.path(), "a.bin", 4096, b'x');
+        let b = create_sized_test_file(dir.path(), "b.bin", 4096, b'y');
+        let sample_a = DupeLs::get_sample_checksum(&a, 4096, 1024, ChecksumAlgorithm::Md5).unwrap();
+        let sample_b = DupeLs::get_sample_checksum(&b, 4096, 1024, ChecksumAlgorithm::Md5).unwrap();
+        assert_eq!(sample_a, sample_b);
+        assert_ne!(
+            DupeLs::get_checksum(&a, ChecksumAlgorithm::Md5).unwrap(),
+            DupeLs::get_checksum(&b, ChecksumAlgorithm::Md5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_unique_samples_are_not_fully_hashed() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.bin"), vec![b'a'; 4096]).unwrap();
+        fs::write(dir.path().join("b.bin"), vec![b'b'; 4096]).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            sample_size: 1,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.entries.lock().unwrap().is_empty());
+        assert_eq!(d.unhashed.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_colliding_samples_are_fully_hashed() {
+        let dir = tempdir().unwrap();
+        create_sized_test_file(dir.path(), "a.bin", 4096, b'x');
+        create_sized_test_file(dir.path(), "b.bin", 4096, b'y');
+        create_sized_test_file(dir.path(), "c.bin", 4096, b'y');
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            sample_size: 1,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let map = d.entries.lock().unwrap();
+        assert_eq!(map.len(), 2);
+        assert!(map.values().any(|paths| paths.len() == 2));
+        assert!(d.unhashed.is_empty());
+    }
+
+    #[test]
+    fn test_parse_sampling_disabled() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.bin"), vec![b'a'; 4096]).unwrap();
+        fs::write(dir.path().join("b.bin"), vec![b'b'; 4096]).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            sample_size: 0,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert_eq!(d.entries.lock().unwrap().len(), 2);
+        assert!(d.unhashed.is_empty());
+    }
+
+    #[test]
+    fn test_parse_huge_sample_size() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.bin"), vec![b'a'; 4096]).unwrap();
+        fs::write(dir.path().join("b.bin"), vec![b'a'; 4096]).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            sample_size: usize::MAX,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        assert_eq!(d.sample_size, u64::MAX);
+        d.parse();
+        assert_eq!(d.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_split_by_contents() {
+        let dir = tempdir().unwrap();
+        let a = create_sized_test_file(dir.path(), "a.bin", 20000, b'x');
+        let b = create_sized_test_file(dir.path(), "b.bin", 20000, b'y');
+        let c = create_sized_test_file(dir.path(), "c.bin", 20000, b'x');
+        let files: Vec<FileEntry> = [&a, &b, &c].iter().map(|p| file_entry(p)).collect();
+        let (error_s, error_r) = unbounded();
+        let split = DupeLs::split_by_contents(files.clone(), &error_s);
+        assert_eq!(split, vec![vec![files[0].clone(), files[2].clone()], vec![files[1].clone()]]);
+        assert!(error_r.is_empty());
+    }
+
+    #[test]
+    fn test_files_equal_different_lengths() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello World");
+        assert!(!DupeLs::files_equal(&a, &b).unwrap());
+        assert!(DupeLs::files_equal(&a, &a).unwrap());
+    }
+
+    #[test]
+    fn test_verify_entries_splits_mismatched_group() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "World");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            verify: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        // Pretend the two files collided on their checksum.
+        let checksum = DupeLs::get_checksum(&a, ChecksumAlgorithm::Md5).unwrap();
+        d.entries.lock().unwrap().insert(
+            checksum.clone(),
+            vec![file_entry(&a), file_entry(&b)],
+        );
+        d.verify_entries();
+        assert_eq!(d.entries.lock().unwrap()[&checksum], vec![file_entry(&a)]);
+        assert_eq!(d.collisions, vec![(checksum, vec![file_entry(&b)])]);
+
+        d.omit = true;
+        assert!(d.get_output_vec().is_empty());
+    }
+
+    #[test]
+    fn test_verify_entries_first_file_deleted() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        let c = create_test_file(dir.path(), "c.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            verify: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        let checksum = DupeLs::get_checksum(&a, ChecksumAlgorithm::Md5).unwrap();
+        d.entries.lock().unwrap().insert(
+            checksum.clone(),
+            vec![file_entry(&a), file_entry(&b), file_entry(&c)],
+        );
+        // The first file vanishes between hashing and verification, the others are still duplicates.
+        fs::remove_file(&a).unwrap();
+        d.verify_entries();
+        assert_eq!(d.entries.lock().unwrap()[&checksum], vec![file_entry(&b), file_entry(&c)]);
+        assert!(d.collisions.is_empty());
+        let errors: Vec<DupeLsError> = d.error_r.try_iter().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path(), a);
+    }
+
+    #[test]
+    fn test_parse_verify_keeps_true_duplicates() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            omit: true,
+            verify: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.collisions.is_empty());
+        // 1.txt, 2.txt, 5.txt and 7.txt all contain "Hello".
+        assert_eq!(d.get_output_vec().len(), 4);
+    }
+
+    #[test]
+    fn test_get_groups() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            track_dot_files: true,
+            recursive: true,
+            depth: 1,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 5);
+
+        let hello = &groups[0];
+        assert_eq!(format!("{:x}", hello.digest.as_ref().unwrap()), "8b1a9953c4611296a827abf8c47804d7");
+        assert_eq!(hello.size, 5);
+        assert_eq!(hello.files.len(), 3);
+        assert!(hello.files.windows(2).all(|w| w[0].path < w[1].path));
+        assert!(hello.files.iter().all(|file| file.size == 5 && file.modified.is_some()));
+
+        // Files with a unique size are listed without a digest.
+        assert!(groups[1..].iter().all(|group| group.digest.is_none() && group.files.len() == 1));
+
+        d.omit = true;
+        assert_eq!(d.get_groups(), vec![hello.clone()]);
+    }
+
+    #[test]
+    fn test_get_output_vec_separator_named_file() {
+        let dir = tempdir().unwrap();
+        create_test_file(dir.path(), ">--", "Hello");
+        create_test_file(dir.path(), "a.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            track_dot_files: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].files[0].path.ends_with(">--"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_dir_with_permission_denied_file() {
         let dir = tempdir().unwrap();
         let _file_path = create_no_read_permission_file(dir.path(), "no_read.txt");
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 0,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
@@ -601,17 +2093,954 @@ mod test {
         let dir = tempdir().unwrap();
         let _dir_path = create_no_read_permission_dir(dir.path(), "no_read_dir");
         let config = DupeLsConfig {
-            base_path: Some(dir.path().to_path_buf()),
+            base_paths: vec![dir.path().to_path_buf()],
             track_dot_files: true,
             recursive: false,
             depth: 0,
             omit: false,
             max_threads: None,
             seperator: "---".to_string(),
+            ..Default::default()
         };
         let mut d = DupeLs::new(config);
         d.parse();
         let output_str = d.get_output_string();
         assert_eq!(output_str.len(), 0);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_collects_permission_denied_errors() {
+        let dir = tempdir().unwrap();
+        let file_path = create_no_read_permission_file(dir.path(), "no_read.txt");
+        let dir_path = create_no_read_permission_dir(dir.path(), "no_read_dir");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let errors = d.take_errors();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], DupeLsError::PermissionDenied { path, dir: false, .. } if *path == file_path));
+        assert!(matches!(&errors[1], DupeLsError::PermissionDenied { path, dir: true, .. } if *path == dir_path));
+        assert!(d.get_errors().is_empty());
+    }
+
+    #[test]
+    fn test_parse_no_errors() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+    }
+
+    #[test]
+    fn test_get_output_string_json() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            format: OutputFormat::Json,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let json: serde_json::Value = serde_json::from_str(&d.get_output_string()).unwrap();
+        let groups = json.as_array().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0]["digest"], "8b1a9953c4611296a827abf8c47804d7");
+        assert_eq!(groups[0]["size"], 5);
+        assert_eq!(groups[0]["files"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_get_output_string_null_terminated() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a\nb.txt", "Hello");
+        let b = create_test_file(dir.path(), "c.txt", "Hello");
+        let c = create_test_file(dir.path(), "d.txt", "Hello World");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            null_terminated: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert_eq!(
+            d.get_output_string(),
+            format!("{}\0{}\0\0{}\0\0", a.to_string_lossy(), b.to_string_lossy(), c.to_string_lossy())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_non_utf8_file_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempdir().unwrap();
+        let latin1 = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        fs::write(&latin1, "Hello").unwrap();
+        let other = create_test_file(dir.path(), "other.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files[0].path, latin1);
+
+        let mut expected = latin1.as_os_str().as_bytes().to_vec();
+        expected.push(b'\n');
+        expected.extend_from_slice(other.as_os_str().as_bytes());
+        assert_eq!(d.get_output_bytes(), expected);
+        assert!(d.get_output_string().contains("caf\\xe9.txt"));
+    }
+
+    #[test]
+    fn test_get_output_escape() {
+        let dir = tempdir().unwrap();
+        create_test_file(dir.path(), "a\nb.txt", "Hello");
+        create_test_file(dir.path(), "c.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            escape: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let output = String::from_utf8(d.get_output_bytes()).unwrap();
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.lines().next().unwrap().ends_with("a\\nb.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_hard_links() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = dir.path().join("b.txt");
+        fs::hard_link(&a, &b).unwrap();
+        let c = create_test_file(dir.path(), "c.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].files[0].path, a);
+        assert_eq!(groups[0].files[0].hard_links, vec![b.clone()]);
+        assert_eq!(groups[0].files[1].path, c);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_hard_links_only_are_not_duplicates() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        fs::hard_link(&a, dir.path().join("b.txt")).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_groups().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_count_hard_links() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = dir.path().join("b.txt");
+        fs::hard_link(&a, &b).unwrap();
+        create_test_file(dir.path(), "c.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            verify: true,
+            count_hard_links: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert_eq!(groups[0].files[0].hard_links, vec![b]);
+        assert_eq!(groups[0].files[1].hard_links, vec![a]);
+        assert!(groups[0].files[2].hard_links.is_empty());
+    }
+
+    #[cfg(unix)]
+    fn setup_symlinks() -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        std::os::unix::fs::symlink(&a, dir.path().join("link1.txt")).unwrap();
+        std::os::unix::fs::symlink(&a, dir.path().join("link2.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+        (dir, a)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_symlinks_ignore() {
+        let (dir, a) = setup_symlinks();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files[0].path, a);
+        assert!(groups[0].files[0].aliases.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_symlinks_follow() {
+        let (dir, a) = setup_symlinks();
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            depth: 10,
+            omit: true,
+            symlinks: SymlinkPolicy::Follow,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].files[0].path, a);
+        assert_eq!(
+            groups[0].files[0].aliases,
+            vec![dir.path().join("link1.txt"), dir.path().join("link2.txt")]
+        );
+        assert_eq!(groups[0].files[1].path, b);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_symlinks_follow_outside_target() {
+        let outside = tempdir().unwrap();
+        let a = create_test_file(outside.path(), "a.txt", "Hello");
+        let dir = tempdir().unwrap();
+        std::os::unix::fs::symlink(&a, dir.path().join("link1.txt")).unwrap();
+        std::os::unix::fs::symlink(&a, dir.path().join("link2.txt")).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            symlinks: SymlinkPolicy::Follow,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_groups().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_symlinks_list() {
+        let (dir, a) = setup_symlinks();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            symlinks: SymlinkPolicy::List,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files[0].path, a);
+        assert_eq!(groups[0].files[0].aliases.len(), 2);
+        assert_eq!(
+            d.get_output_vec(),
+            vec![
+                a.to_string_lossy().into_owned(),
+                format!("-> {}", dir.path().join("link1.txt").display()),
+                format!("-> {}", dir.path().join("link2.txt").display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_exclude_prunes_directories() {
+        let (dir, _files) = setup_test_files();
+        let exclude = [parse_glob("more_more_files").unwrap(), parse_glob("2.txt").unwrap()];
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            omit: true,
+            filter: PathFilter::new(&exclude, &[]),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        // Only 1.txt and more_files/5.txt are left, 2.txt and more_more_files/7.txt are excluded.
+        assert_eq!(groups[0].files.len(), 2);
+        assert!(groups[0].files.iter().all(|file| !file.path.ends_with("2.txt") && !file.path.ends_with("7.txt")));
+    }
+
+    #[test]
+    fn test_parse_include() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            filter: PathFilter::new(&[], &[parse_glob("more_files/*").unwrap()]),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert!(groups.iter().flat_map(|group| &group.files).all(|file| file.path.parent().unwrap().ends_with("more_files")));
+        assert_eq!(groups.iter().map(|group| group.files.len()).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_parse_respect_ignore() {
+        let (dir, _files) = setup_test_files();
+        fs::write(dir.path().join(".gitignore"), "2.txt\nmore_more_files/\n").unwrap();
+        fs::write(dir.path().join("more_files").join(".ignore"), "*.txt\n!5.txt\n").unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        create_test_file(&dir.path().join(".git"), "HEAD.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            omit: true,
+            respect_ignore: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert!(groups[0].files[0].path.ends_with("1.txt"));
+        assert!(groups[0].files[1].path.ends_with("more_files/5.txt"));
+    }
+
+    #[test]
+    fn test_parse_respect_parent_ignore() {
+        let repo = tempdir().unwrap();
+        fs::create_dir(repo.path().join(".git")).unwrap();
+        fs::write(repo.path().join(".gitignore"), "*.log\n/sub/b.txt\n").unwrap();
+        let sub = repo.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        for name in ["a.txt", "b.txt", "c.log", "d.txt"] {
+            create_test_file(&sub, name, "Hello");
+        }
+        let config = DupeLsConfig {
+            base_paths: vec![sub.clone()],
+            omit: true,
+            respect_ignore: true,
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        let paths: Vec<&Path> = groups[0].files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(paths, vec![sub.join("a.txt"), sub.join("d.txt")]);
+    }
+
+    #[test]
+    fn test_parse_types() {
+        let dir = tempdir().unwrap();
+        let wav = b"RIFF\x24\0\0\0WAVEfmt ";
+        fs::write(dir.path().join("kick.wav"), wav).unwrap();
+        fs::write(dir.path().join("kick copy"), wav).unwrap();
+        create_test_file(dir.path(), "README.md", "Hello");
+        create_test_file(dir.path(), "notes.md", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            types: vec![FileType::Audio],
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        assert!(groups[0].files[0].path.ends_with("kick copy"));
+    }
+
+    #[test]
+    fn test_parse_min_max_size() {
+        let (dir, _files) = setup_test_files();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            min_size: 9,
+            max_size: Some(20),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].files[0].path.ends_with("3.txt"));
+    }
+
+    #[test]
+    fn test_parse_empty_files() {
+        let dir = tempdir().unwrap();
+        create_test_file(dir.path(), "a.txt", "");
+        create_test_file(dir.path(), "b.txt", "");
+        create_test_file(dir.path(), "c.txt", "Hello");
+        let config = |empty_files| DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            empty_files,
+            ..Default::default()
+        };
+
+        let mut d = DupeLs::new(config(EmptyFilePolicy::Skip));
+        d.parse();
+        assert_eq!(d.get_output_vec().len(), 1);
+
+        let mut d = DupeLs::new(config(EmptyFilePolicy::List));
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 2);
+        assert!(groups[1].digest.is_none());
+        assert_eq!(groups[1].size, 0);
+        assert_eq!(groups[1].files.len(), 2);
+        assert!(d.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_multiple_roots() {
+        let old = tempdir().unwrap();
+        let new = tempdir().unwrap();
+        let a = create_test_file(old.path(), "a.txt", "Hello");
+        create_test_file(old.path(), "b.txt", "World");
+        create_test_file(old.path(), "c.txt", "World");
+        let d_txt = create_test_file(new.path(), "d.txt", "Hello");
+        let config = |cross_root| DupeLsConfig {
+            base_paths: vec![old.path().to_path_buf(), new.path().to_path_buf(), old.path().to_path_buf()],
+            omit: true,
+            cross_root,
+            ..Default::default()
+        };
+
+        let mut d = DupeLs::new(config(false));
+        d.parse();
+        assert_eq!(d.get_groups().len(), 2);
+
+        let mut d = DupeLs::new(config(true));
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+        let roots: Vec<_> = groups[0].files.iter().map(|file| (&file.path, file.root)).collect();
+        assert!(roots.contains(&(&a, 0)));
+        assert!(roots.contains(&(&d_txt, 1)));
+    }
+
+    #[test]
+    fn test_parse_reference_paths() {
+        let (library, _files) = setup_test_files();
+        let uploads = tempdir().unwrap();
+        let upload = create_test_file(uploads.path(), "upload.txt", "Hello");
+        create_test_file(uploads.path(), "new.txt", "Something new");
+        create_test_file(uploads.path(), "new copy.txt", "Something new");
+        let config = DupeLsConfig {
+            base_paths: vec![uploads.path().to_path_buf()],
+            reference_paths: vec![library.path().to_path_buf()],
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+        assert_eq!(groups[0].references().count(), 2);
+        assert_eq!(
+            d.get_output_vec(),
+            vec![format!("{}\t{}", upload.display(), library.path().join("1.txt").display())]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_skips_special_files() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let socket = dir.path().join("socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert_eq!(d.get_output_vec(), vec![a.to_string_lossy().to_string()]);
+        let errors = d.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], DupeLsError::NotRegular { kind: "socket", .. }));
+        assert_eq!(errors[0].path(), socket);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_one_file_system() {
+        let dir = tempdir().unwrap();
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        // Needs a directory on another filesystem, /dev/shm is usually a tmpfs.
+        let Ok(other) = tempfile::tempdir_in("/dev/shm") else {
+            return;
+        };
+        let device = |path: &std::path::Path| FileEntry::file_id(&fs::metadata(path).unwrap()).unwrap().0;
+        if device(dir.path()) == device(other.path()) {
+            return;
+        }
+        create_test_file(other.path(), "b.txt", "Hello");
+        std::os::unix::fs::symlink(other.path(), dir.path().join("mount")).unwrap();
+        let config = |one_file_system| DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            symlinks: SymlinkPolicy::Follow,
+            one_file_system,
+            ..Default::default()
+        };
+
+        let mut d = DupeLs::new(config(false));
+        d.parse();
+        assert_eq!(d.get_groups()[0].files.len(), 2);
+
+        let mut d = DupeLs::new(config(true));
+        d.parse();
+        assert_eq!(d.get_output_vec(), vec![a.to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn test_parse_walk_threads() {
+        let dir = tempdir().unwrap();
+        for i in 0..20 {
+            let sub = dir.path().join(format!("dir{}", i));
+            fs::create_dir_all(sub.join("nested")).unwrap();
+            create_test_file(&sub, "a.txt", "Hello");
+            create_test_file(&sub.join("nested"), "b.txt", &format!("Unique {}", i));
+        }
+        let config = |walk_threads| DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            recursive: true,
+            depth: 3,
+            walk_threads: Some(walk_threads),
+            ..Default::default()
+        };
+
+        let mut d = DupeLs::new(config(1));
+        d.parse();
+        let expected = d.get_output_vec();
+        assert_eq!(expected.len(), 40 + 20); // 40 files in 21 groups.
+
+        for _ in 0..5 {
+            let mut d = DupeLs::new(config(8));
+            d.parse();
+            assert_eq!(d.get_output_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn test_walk_queue_finishes_panicked_directory() {
+        let queue = WalkQueue {
+            injector: Injector::new(),
+            stealers: Vec::new(),
+            pending: AtomicUsize::new(1),
+            lock: Mutex::new(()),
+            idle: Condvar::new(),
+        };
+        thread::scope(|scope| {
+            let walker = scope.spawn(|| {
+                let _walking = Walking(&queue);
+                panic!("walk failed");
+            });
+            // Returns once the panicking walker drops its directory instead of waiting forever.
+            assert!(!queue.wait());
+            assert!(walker.join().is_err());
+        });
+    }
+
+    #[test]
+    fn test_parse_cache() {
+        let dir = tempdir().unwrap();
+        let cache_dir = tempdir().unwrap();
+        create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        // The cache stays open until the DupeLs is dropped, so each scan gets its own.
+        let scan = |purge_cache| {
+            let config = DupeLsConfig {
+                base_paths: vec![dir.path().to_path_buf()],
+                omit: true,
+                cache_dir: Some(cache_dir.path().to_path_buf()),
+                purge_cache,
+                ..Default::default()
+            };
+            let mut d = DupeLs::new(config);
+            d.parse();
+            assert!(d.get_errors().is_empty());
+            d.get_groups().len()
+        };
+        assert_eq!(scan(false), 1);
+
+        // Same size and modification time, so the cached checksum is still used.
+        let modified = fs::metadata(&b).unwrap().modified().unwrap();
+        fs::write(&b, "World").unwrap();
+        File::options().write(true).open(&b).unwrap().set_modified(modified).unwrap();
+        assert_eq!(scan(false), 1);
+
+        assert_eq!(scan(true), 0);
+    }
+
+    #[test]
+    fn test_parse_cache_new_copy() {
+        let dir = tempdir().unwrap();
+        let cache_dir = tempdir().unwrap();
+        // Larger than twice the sample size, so an uncached file of this size would be sampled.
+        let contents = "x".repeat(20 * 1024);
+        create_test_file(dir.path(), "a.bin", &contents);
+        create_test_file(dir.path(), "b.bin", &contents);
+        let scan = || {
+            let config = DupeLsConfig {
+                base_paths: vec![dir.path().to_path_buf()],
+                omit: true,
+                cache_dir: Some(cache_dir.path().to_path_buf()),
+                ..Default::default()
+            };
+            let mut d = DupeLs::new(config);
+            d.parse();
+            assert!(d.get_errors().is_empty());
+            d.get_groups()
+        };
+        assert_eq!(scan().len(), 1);
+
+        // The new copy is the only file that isn't cached, it still joins the cached files.
+        create_test_file(dir.path(), "c.bin", &contents);
+        let groups = scan();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_xattr_cache() {
+        let dir = tempdir().unwrap();
+        create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        let scan = || {
+            let config = DupeLsConfig {
+                base_paths: vec![dir.path().to_path_buf()],
+                omit: true,
+                xattr_cache: true,
+                ..Default::default()
+            };
+            let mut d = DupeLs::new(config);
+            d.parse();
+            d.get_groups()
+        };
+        assert_eq!(scan().len(), 1);
+        if xattr::get(&b, "user.dupels.md5").ok().flatten().is_none() {
+            return; // The filesystem doesn't support user extended attributes.
+        }
+
+        // The checksum is found again after a rename, as long as the size and modification time are unchanged.
+        let c = dir.path().join("c.txt");
+        fs::rename(&b, &c).unwrap();
+        let modified = fs::metadata(&c).unwrap().modified().unwrap();
+        fs::write(&c, "World").unwrap();
+        let file = File::options().write(true).open(&c).unwrap();
+        file.set_modified(modified).unwrap();
+        assert_eq!(scan().len(), 1);
+
+        file.set_modified(modified + std::time::Duration::from_secs(1)).unwrap();
+        assert!(scan().is_empty());
+
+        // A new copy of cached files too large to be hashed without sampling still joins them.
+        let contents = "x".repeat(20 * 1024);
+        create_test_file(dir.path(), "a.bin", &contents);
+        create_test_file(dir.path(), "b.bin", &contents);
+        assert_eq!(scan().len(), 1);
+        create_test_file(dir.path(), "c.bin", &contents);
+        let groups = scan();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_snapshot() {
+        let dir = tempdir().unwrap();
+        let snapshots = tempdir().unwrap();
+        let snapshot = snapshots.path().join("snapshot.json");
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        create_test_file(dir.path(), "c.txt", "World");
+        let scan = || {
+            let config = DupeLsConfig {
+                base_paths: vec![dir.path().to_path_buf()],
+                save_snapshot: Some(snapshot.clone()),
+                since_snapshot: Some(snapshot.clone()),
+                ..Default::default()
+            };
+            let mut d = DupeLs::new(config);
+            d.parse();
+            d
+        };
+
+        // There is no snapshot yet, so all groups are listed.
+        let d = scan();
+        assert_eq!(d.get_errors().len(), 1);
+        assert!(matches!(d.get_errors()[0], DupeLsError::Snapshot { .. }));
+        assert_eq!(d.get_output_vec().len(), 4);
+
+        let d = scan();
+        assert!(d.get_errors().is_empty());
+        assert_eq!(d.get_output_string(), "");
+
+        fs::remove_file(&b).unwrap();
+        let d = scan();
+        let a = a.to_string_lossy();
+        let b = b.to_string_lossy();
+        assert_eq!(
+            d.get_output_string(),
+            format!("resolved 8b1a9953c4611296a827abf8c47804d7 5\n  {}\n- {}", a, b)
+        );
+        assert_eq!(d.get_output_bytes(), d.get_output_string().into_bytes());
+
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            hash: ChecksumAlgorithm::Sha256,
+            since_snapshot: Some(snapshot.clone()),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert_eq!(d.get_errors()[0].to_string(), format!(
+            "Could not use snapshot '{}': it was made with --hash md5, not sha256",
+            snapshot.display()
+        ));
+    }
+
+    #[test]
+    fn test_parse_snapshot_ignores_filters() {
+        let dir = tempdir().unwrap();
+        let snapshots = tempdir().unwrap();
+        let snapshot = snapshots.path().join("snapshot.json");
+        let manifest = snapshots.path().join("MD5SUMS");
+        fs::write(&manifest, "8b1a9953c4611296a827abf8c47804d7  /tape/hello.txt\n").unwrap();
+        create_test_file(dir.path(), "a.txt", "Hello");
+        create_test_file(dir.path(), "b.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            cross_root: true,
+            save_snapshot: Some(snapshot.clone()),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_groups().is_empty());
+
+        // The group hidden by --cross-root is saved.
+        let saved = Snapshot::load(&snapshot).unwrap();
+        assert_eq!(saved.groups.len(), 1);
+        assert_eq!(saved.groups[0].files, vec![dir.path().join("a.txt"), dir.path().join("b.txt")]);
+
+        // So is the group without a reference file when there are reference directories.
+        let references = tempdir().unwrap();
+        create_test_file(references.path(), "x.txt", "Hi");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            reference_paths: vec![references.path().to_path_buf()],
+            save_snapshot: Some(snapshot.clone()),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_groups().is_empty());
+        assert_eq!(Snapshot::load(&snapshot).unwrap(), saved);
+
+        // The file from the manifest isn't compared or saved.
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            from_manifests: vec![manifest],
+            save_snapshot: Some(snapshot.clone()),
+            since_snapshot: Some(snapshot.clone()),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+        assert_eq!(d.get_output_string(), "");
+        assert_eq!(Snapshot::load(&snapshot).unwrap(), saved);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_snapshot_escape() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempdir().unwrap();
+        let snapshots = tempdir().unwrap();
+        let snapshot = snapshots.path().join("snapshot.json");
+        create_test_file(dir.path(), "a.txt", "Hello");
+        let name = std::ffi::OsStr::from_bytes(b"b\n\xe9.txt");
+        fs::write(dir.path().join(name), "Hello").unwrap();
+        let scan = |escape: bool| {
+            let config = DupeLsConfig {
+                base_paths: vec![dir.path().to_path_buf()],
+                escape,
+                since_snapshot: Some(snapshot.clone()),
+                ..Default::default()
+            };
+            let mut d = DupeLs::new(config);
+            d.parse();
+            d
+        };
+        fs::write(&snapshot, r#"{"hash":"md5","groups":[]}"#).unwrap();
+
+        let p = dir.path().to_str().unwrap();
+        let d = scan(false);
+        let mut expected = format!("new 8b1a9953c4611296a827abf8c47804d7 5\n+ {p}/a.txt\n+ {p}/").into_bytes();
+        expected.extend_from_slice(b"b\n\xe9.txt");
+        assert_eq!(d.get_output_bytes(), expected);
+        assert_eq!(
+            scan(true).get_output_string(),
+            format!("new 8b1a9953c4611296a827abf8c47804d7 5\n+ {p}/a.txt\n+ {p}/b\\n\\xe9.txt")
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let (dir, _files) = setup_test_files();
+        let manifest = tempdir().unwrap();
+        let manifest = manifest.path().join("MD5SUMS");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+        let lines: Vec<String> = fs::read_to_string(&manifest).unwrap().lines().map(String::from).collect();
+        let mut expected = Vec::new();
+        for name in ["1.txt", "2.txt", "3.txt"] {
+            let path = dir.path().join(name);
+            let digest = DupeLs::get_checksum(&path, ChecksumAlgorithm::Md5).unwrap();
+            expected.push(format!("{:x}  {}", digest, path.display()));
+        }
+        assert_eq!(lines, expected);
+        // Unique files are hashed for the manifest, but still omitted from the output.
+        assert!(d.get_groups().iter().all(|group| group.files.len() > 1));
+    }
+
+    #[test]
+    fn test_parse_from_manifests() {
+        let (dir, _files) = setup_test_files();
+        let manifests = tempdir().unwrap();
+        let manifest = manifests.path().join("MD5SUMS");
+        fs::write(
+            &manifest,
+            "8b1a9953c4611296a827abf8c47804d7  /tape/hello.txt\n\
+             b10a8db164e0754105b7a99be72e3fe5  /tape/world.txt\n\
+             00112233445566778899aabbccddeeff  /tape/only.txt\n\
+             not a checksum\n",
+        )
+        .unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            from_manifests: vec![manifest.clone(), manifests.path().join("missing.md5")],
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert_eq!(d.get_errors().len(), 2);
+        assert!(d.get_errors().iter().all(|e| matches!(e, DupeLsError::Manifest { .. })));
+
+        let groups = d.get_groups();
+        let group_of = |name: &str| {
+            groups.iter().find(|group| group.files.iter().any(|file| file.path == Path::new(name))).unwrap()
+        };
+        let hello = group_of("/tape/hello.txt");
+        let paths: Vec<&Path> = hello.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![Path::new("/tape/hello.txt"), &dir.path().join("1.txt"), &dir.path().join("2.txt")]
+        );
+        assert_eq!(hello.size, 5);
+        assert!(hello.spans_roots());
+        let tape = hello.files.iter().find(|file| file.path == Path::new("/tape/hello.txt")).unwrap();
+        assert_eq!((tape.size, tape.root), (5, 1));
+        assert_eq!(group_of("/tape/world.txt").files.len(), 2);
+        assert_eq!(group_of("/tape/only.txt").files[0].size, 0);
+    }
+
+    #[test]
+    fn test_parse_from_manifests_skips_found_paths() {
+        let dir = tempdir().unwrap();
+        let manifests = tempdir().unwrap();
+        let manifest = manifests.path().join("MD5SUMS");
+        let a = create_test_file(dir.path(), "a.txt", "Hello");
+        let b = create_test_file(dir.path(), "b.txt", "Hello");
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        };
+        DupeLs::new(config).parse();
+
+        // Every path in the manifest was found again, and the copy on tape is listed once.
+        let mut contents = fs::read_to_string(&manifest).unwrap();
+        contents.push_str("8b1a9953c4611296a827abf8c47804d7  /tape/hello.txt\n");
+        fs::write(&manifest, contents).unwrap();
+        let other = manifests.path().join("TAPE.md5");
+        fs::write(&other, "8b1a9953c4611296a827abf8c47804d7  /tape/hello.txt\n").unwrap();
+        let config = DupeLsConfig {
+            base_paths: vec![dir.path().to_path_buf()],
+            omit: true,
+            from_manifests: vec![manifest.clone(), other],
+            ..Default::default()
+        };
+        let mut d = DupeLs::new(config);
+        d.parse();
+        assert!(d.get_errors().is_empty());
+        let groups = d.get_groups();
+        assert_eq!(groups.len(), 1);
+        let files: Vec<(&Path, usize)> = groups[0].files.iter().map(|file| (file.path.as_path(), file.root)).collect();
+        assert_eq!(files, vec![(Path::new("/tape/hello.txt"), 1), (a.as_path(), 0), (b.as_path(), 0)]);
+    }
+
+    #[test]
+    fn test_from_manifests_without_base_paths() {
+        let config = DupeLsConfig {
+            from_manifests: vec![PathBuf::from("MD5SUMS")],
+            ..Default::default()
+        };
+        assert!(DupeLs::new(config).base_paths.is_empty());
+    }
 }
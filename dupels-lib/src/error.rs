@@ -0,0 +1,135 @@
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// An error for a file or directory that was skipped during a scan.
+///
+/// `dir` is `true` if the path is a directory that could not be walked.
+#[derive(Debug)]
+pub enum DupeLsError {
+    /// The path exists but can't be read with the current permissions.
+    PermissionDenied { path: PathBuf, dir: bool, source: io::Error },
+    /// The path was removed after it was found by the walk.
+    Vanished { path: PathBuf, dir: bool },
+    /// Any other IO error.
+    Io { path: PathBuf, dir: bool, source: io::Error },
+    /// The path is not a regular file, e.g. a FIFO, socket or device node, so it is never read.
+    NotRegular { path: PathBuf, kind: &'static str },
+    /// The checksum cache could not be opened or written, the files are hashed without it.
+    Cache { path: PathBuf, source: io::Error },
+    /// A snapshot could not be read or written.
+    Snapshot { path: PathBuf, source: io::Error },
+    /// A checksum manifest could not be read or written, or holds lines that could not be read.
+    Manifest { path: PathBuf, source: io::Error },
+}
+
+impl DupeLsError {
+    pub(crate) fn file(path: &Path, err: io::Error) -> DupeLsError {
+        DupeLsError::from_io(path, false, err)
+    }
+
+    pub(crate) fn dir(path: &Path, err: io::Error) -> DupeLsError {
+        DupeLsError::from_io(path, true, err)
+    }
+
+    fn from_io(path: &Path, dir: bool, err: io::Error) -> DupeLsError {
+        let path = path.to_path_buf();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => DupeLsError::PermissionDenied { path, dir, source: err },
+            io::ErrorKind::NotFound => DupeLsError::Vanished { path, dir },
+            _ => DupeLsError::Io { path, dir, source: err },
+        }
+    }
+
+    /// The path of the skipped file or directory.
+    pub fn path(&self) -> &Path {
+        match self {
+            DupeLsError::PermissionDenied { path, .. }
+            | DupeLsError::Vanished { path, .. }
+            | DupeLsError::Io { path, .. }
+            | DupeLsError::NotRegular { path, .. }
+            | DupeLsError::Cache { path, .. }
+            | DupeLsError::Snapshot { path, .. }
+            | DupeLsError::Manifest { path, .. } => path,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        match self {
+            DupeLsError::PermissionDenied { dir, .. }
+            | DupeLsError::Vanished { dir, .. }
+            | DupeLsError::Io { dir, .. } => *dir,
+            DupeLsError::NotRegular { .. }
+            | DupeLsError::Cache { .. }
+            | DupeLsError::Snapshot { .. }
+            | DupeLsError::Manifest { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for DupeLsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.is_dir() { "directory" } else { "file" };
+        match self {
+            DupeLsError::PermissionDenied { path, source, .. } => {
+                write!(f, "Could not open {} '{}': {}", kind, path.display(), source)
+            }
+            DupeLsError::Vanished { path, .. } => {
+                write!(f, "Could not open {} '{}': it no longer exists", kind, path.display())
+            }
+            DupeLsError::Io { path, source, .. } => {
+                write!(f, "Error reading {} '{}': {}", kind, path.display(), source)
+            }
+            DupeLsError::NotRegular { path, kind } => {
+                write!(f, "Skipped {} '{}': not a regular file", kind, path.display())
+            }
+            DupeLsError::Cache { path, source } => {
+                write!(f, "Could not use checksum cache '{}': {}", path.display(), source)
+            }
+            DupeLsError::Snapshot { path, source } => {
+                write!(f, "Could not use snapshot '{}': {}", path.display(), source)
+            }
+            DupeLsError::Manifest { path, source } => {
+                write!(f, "Could not use manifest '{}': {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl error::Error for DupeLsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DupeLsError::PermissionDenied { source, .. }
+            | DupeLsError::Io { source, .. }
+            | DupeLsError::Cache { source, .. }
+            | DupeLsError::Snapshot { source, .. }
+            | DupeLsError::Manifest { source, .. } => Some(source),
+            DupeLsError::Vanished { .. } | DupeLsError::NotRegular { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_classification() {
+        let denied = DupeLsError::file(Path::new("a.txt"), io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(denied, DupeLsError::PermissionDenied { dir: false, .. }));
+        assert!(denied.to_string().starts_with("Could not open file 'a.txt'"));
+
+        let vanished = DupeLsError::dir(Path::new("some_dir"), io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(vanished, DupeLsError::Vanished { dir: true, .. }));
+        assert_eq!(vanished.to_string(), "Could not open directory 'some_dir': it no longer exists");
+        assert_eq!(vanished.path(), Path::new("some_dir"));
+
+        let other = DupeLsError::file(Path::new("b.txt"), io::Error::other("disk on fire"));
+        assert!(matches!(other, DupeLsError::Io { .. }));
+        assert_eq!(other.to_string(), "Error reading file 'b.txt': disk on fire");
+
+        let fifo = DupeLsError::NotRegular { path: PathBuf::from("pipe"), kind: "FIFO" };
+        assert_eq!(fifo.to_string(), "Skipped FIFO 'pipe': not a regular file");
+    }
+}
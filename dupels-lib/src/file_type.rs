@@ -0,0 +1,116 @@
+use clap::ValueEnum;
+use std::{io, path::Path};
+
+/// A category of files selected with `--type`.
+///
+/// A file belongs to a category if its extension is listed for it, or otherwise if its first bytes
+/// match a known format of that category, so misnamed files are still found.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Audio,
+    Image,
+    Video,
+    Document,
+    Archive,
+}
+
+impl FileType {
+    /// The lowercase extensions, without the dot, of the category.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            FileType::Audio => &[
+                "aac", "aif", "aifc", "aiff", "amr", "ape", "au", "flac", "m4a", "mid", "midi", "mp3", "oga", "ogg",
+                "opus", "wav", "wma",
+            ],
+            FileType::Image => &[
+                "arw", "avif", "bmp", "cr2", "dng", "gif", "heic", "heif", "ico", "jpeg", "jpg", "jxl", "nef", "png",
+                "psd", "svg", "tif", "tiff", "webp",
+            ],
+            FileType::Video => &[
+                "3gp", "avi", "flv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "ogv", "webm", "wmv",
+            ],
+            FileType::Document => &[
+                "doc", "docx", "epub", "key", "md", "numbers", "odp", "ods", "odt", "pages", "pdf", "ppt", "pptx",
+                "rtf", "txt", "xls", "xlsx",
+            ],
+            FileType::Archive => &[
+                "7z", "bz2", "cab", "dmg", "gz", "iso", "lz", "lz4", "lzma", "rar", "tar", "tgz", "xz", "zip", "zst",
+            ],
+        }
+    }
+
+    /// Returns the category of a file extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<FileType> {
+        let extension = extension.to_ascii_lowercase();
+        FileType::value_variants()
+            .iter()
+            .find(|file_type| file_type.extensions().contains(&extension.as_str()))
+            .copied()
+    }
+
+    /// Reads the first bytes of the file at `path` and returns the category of the format they match.
+    pub fn sniff(path: &Path) -> io::Result<Option<FileType>> {
+        let file_type = infer::get_from_path(path)?.and_then(|kind| match kind.matcher_type() {
+            infer::MatcherType::Audio => Some(FileType::Audio),
+            infer::MatcherType::Image => Some(FileType::Image),
+            infer::MatcherType::Video => Some(FileType::Video),
+            infer::MatcherType::Doc | infer::MatcherType::Book => Some(FileType::Document),
+            infer::MatcherType::Archive => Some(FileType::Archive),
+            _ => None,
+        });
+        Ok(file_type)
+    }
+
+    /// Returns `true` if the file at `path` belongs to one of `types`. The contents are only read if the
+    /// extension doesn't match.
+    pub(crate) fn matches(types: &[FileType], path: &Path) -> io::Result<bool> {
+        let by_extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(FileType::from_extension);
+        if by_extension.is_some_and(|file_type| types.contains(&file_type)) {
+            return Ok(true);
+        }
+        Ok(FileType::sniff(path)?.is_some_and(|file_type| types.contains(&file_type)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(FileType::from_extension("WAV"), Some(FileType::Audio));
+        assert_eq!(FileType::from_extension("jpeg"), Some(FileType::Image));
+        assert_eq!(FileType::from_extension("rs"), None);
+    }
+
+    #[test]
+    fn test_extensions_are_unique() {
+        for file_type in FileType::value_variants() {
+            for extension in file_type.extensions() {
+                assert_eq!(FileType::from_extension(extension), Some(*file_type));
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_sniffs_misnamed_files() {
+        let dir = tempdir().unwrap();
+        let png = dir.path().join("kick.dat");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let wav = dir.path().join("snare");
+        fs::write(&wav, b"RIFF\x24\0\0\0WAVEfmt ").unwrap();
+        let readme = dir.path().join("README");
+        fs::write(&readme, "Hello").unwrap();
+
+        assert!(FileType::matches(&[FileType::Image], &png).unwrap());
+        assert!(FileType::matches(&[FileType::Audio], &wav).unwrap());
+        assert!(!FileType::matches(&[FileType::Audio], &png).unwrap());
+        assert!(!FileType::matches(&[FileType::Audio, FileType::Image], &readme).unwrap());
+        assert!(FileType::matches(&[FileType::Audio], Path::new("missing.mp3")).unwrap());
+    }
+}
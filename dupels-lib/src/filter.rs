@@ -0,0 +1,118 @@
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use std::path::Path;
+
+/// Decides which files and directories the walk visits, based on `--exclude` and `--include` globs.
+///
+/// A glob matches if it matches either the path relative to the base directory or the name of the
+/// file or directory, so `node_modules` excludes every directory with that name while `src/*.rs` only
+/// matches below `src`. Excluded directories are not walked at all. Include globs only apply to files.
+#[derive(Clone, Debug, Default)]
+pub struct PathFilter {
+    exclude: Vec<GlobMatcher>,
+    include: Vec<GlobMatcher>,
+}
+
+impl PathFilter {
+    /// Builds the filter. A `Glob` is checked when it is parsed, so unlike a `GlobSet` this can't fail.
+    pub fn new(exclude: &[Glob], include: &[Glob]) -> PathFilter {
+        PathFilter {
+            exclude: exclude.iter().map(Glob::compile_matcher).collect(),
+            include: include.iter().map(Glob::compile_matcher).collect(),
+        }
+    }
+
+    fn matches(globs: &[GlobMatcher], path: &Path) -> bool {
+        globs
+            .iter()
+            .any(|glob| glob.is_match(path) || path.file_name().is_some_and(|name| glob.is_match(name)))
+    }
+
+    /// Returns `true` if the directory at `path`, relative to the base directory, should be walked.
+    pub fn walk_dir(&self, path: &Path) -> bool {
+        !PathFilter::matches(&self.exclude, path)
+    }
+
+    /// Returns `true` if the file at `path`, relative to the base directory, should be listed.
+    pub fn keep_file(&self, path: &Path) -> bool {
+        !PathFilter::matches(&self.exclude, path) && (self.include.is_empty() || PathFilter::matches(&self.include, path))
+    }
+}
+
+/// Parses a glob given on the command line. Like in `.gitignore` files `*` doesn't match `/`, use `**` to match
+/// any number of directories. A trailing `/` is dropped so `target/` matches the directory `target`.
+pub(crate) fn parse_glob(glob: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(glob.strip_suffix('/').unwrap_or(glob))
+        .literal_separator(true)
+        .build()
+}
+
+/// Parses a size given on the command line, in bytes or with a `K`, `M`, `G` or `T` suffix for powers of 1024.
+/// The suffix may be followed by `B` or `iB`, so `10K`, `10KB` and `10KiB` are the same size.
+pub(crate) fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let digits = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits);
+    let number: u64 = number.parse().map_err(|_| format!("'{}' is not a size", size))?;
+    let unit = unit.trim_start().to_ascii_uppercase();
+    let shift = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("'{}' is not a size, use a suffix of K, M, G or T", size)),
+    };
+    number.checked_mul(1 << shift).ok_or_else(|| format!("'{}' is too large", size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filter(exclude: &[&str], include: &[&str]) -> PathFilter {
+        let globs = |globs: &[&str]| globs.iter().map(|glob| parse_glob(glob).unwrap()).collect::<Vec<_>>();
+        PathFilter::new(&globs(exclude), &globs(include))
+    }
+
+    #[test]
+    fn test_default_keeps_everything() {
+        let filter = PathFilter::default();
+        assert!(filter.walk_dir(Path::new("node_modules")));
+        assert!(filter.keep_file(Path::new("a/b.txt")));
+    }
+
+    #[test]
+    fn test_exclude_matches_name_or_path() {
+        let filter = filter(&["node_modules", "target/", "src/*.rs"], &[]);
+        assert!(!filter.walk_dir(Path::new("web/node_modules")));
+        assert!(!filter.walk_dir(Path::new("target")));
+        assert!(filter.walk_dir(Path::new("src")));
+        assert!(!filter.keep_file(Path::new("src/main.rs")));
+        assert!(filter.keep_file(Path::new("tests/main.rs")));
+        assert!(filter.keep_file(Path::new("src/bin/main.rs")));
+    }
+
+    #[test]
+    fn test_include_only_applies_to_files() {
+        let filter = filter(&["*.tmp.jpg"], &["*.jpg"]);
+        assert!(filter.walk_dir(Path::new("photos")));
+        assert!(filter.keep_file(Path::new("photos/a.jpg")));
+        assert!(!filter.keep_file(Path::new("photos/a.png")));
+        assert!(!filter.keep_file(Path::new("photos/a.tmp.jpg")));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("4m"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1 TB"), Ok(1024 * 1024 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+}
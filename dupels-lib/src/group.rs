@@ -0,0 +1,107 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use crate::Digest;
+
+/// A file found while walking the directory tree.
+///
+/// # Fields
+/// - `path`: The path of the file, kept as the raw OS string so non-UTF-8 names can be opened again.
+/// - `size`: The size of the file in bytes.
+/// - `modified`: The last modification time of the file, if the platform reports one.
+/// - `hard_links`: The other paths found by the walk that are hard links to the same file, sorted by path.
+/// - `aliases`: The symlinks found by the walk that point at the same file, sorted by path.
+/// - `symlink`: `true` if the path goes through a symlink, either to the file itself or to a parent directory.
+/// - `root`: The index of the root directory the file was found in, in the order the roots were given.
+///   Reference directories are numbered after the other roots, followed by the manifests read with `from_manifests`.
+/// - `reference`: `true` if the file was found in a reference directory.
+/// - `file_id`: The device and inode number of the file, `None` on platforms without inodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub hard_links: Vec<PathBuf>,
+    pub aliases: Vec<PathBuf>,
+    pub symlink: bool,
+    pub root: usize,
+    pub reference: bool,
+    pub(crate) file_id: Option<(u64, u64)>,
+}
+
+impl FileEntry {
+    pub(crate) fn new(path: PathBuf, metadata: &fs::Metadata) -> FileEntry {
+        FileEntry {
+            path,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            hard_links: Vec::new(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: FileEntry::file_id(metadata),
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// A group of files with the same contents.
+///
+/// # Fields
+/// - `digest`: The checksum shared by the files. `None` if the file was never hashed because no other
+///   file has the same size or head and tail sample.
+/// - `size`: The size in bytes of each file in the group.
+/// - `files`: The files in the group, sorted by path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub digest: Option<Digest>,
+    pub size: u64,
+    pub files: Vec<FileEntry>,
+}
+
+impl DuplicateGroup {
+    pub(crate) fn new(digest: Option<Digest>, mut files: Vec<FileEntry>) -> DuplicateGroup {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        DuplicateGroup {
+            digest,
+            size: files.first().map_or(0, |file| file.size),
+            files,
+        }
+    }
+
+    /// Returns `true` if the group holds more than one file.
+    pub fn is_duplicate(&self) -> bool {
+        self.files.len() > 1
+    }
+
+    /// The files of the group that were found in a reference directory.
+    pub fn references(&self) -> impl Iterator<Item = &FileEntry> {
+        self.files.iter().filter(|file| file.reference)
+    }
+
+    /// Returns `true` if the group holds both files from a reference directory and files from elsewhere.
+    pub fn has_reference(&self) -> bool {
+        self.references().next().is_some() && self.files.iter().any(|file| !file.reference)
+    }
+
+    /// The hard links of `file` that aren't listed in the group as files of their own, which they are
+    /// when hard links are counted as duplicates.
+    pub fn unlisted_hard_links<'a>(&'a self, file: &'a FileEntry) -> impl Iterator<Item = &'a PathBuf> {
+        file.hard_links.iter().filter(|path| self.files.iter().all(|other| &other.path != *path))
+    }
+
+    /// Returns `true` if the files of the group were found in more than one root directory.
+    pub fn spans_roots(&self) -> bool {
+        self.files.iter().any(|file| file.root != self.files[0].root)
+    }
+}
@@ -1,13 +1,31 @@
+mod cache;
+mod checksum;
 mod cli;
 mod gui;
 mod dupels;
+mod error;
+mod file_type;
+mod filter;
+mod group;
+mod manifest;
+mod output;
+mod snapshot;
 
+pub use cache::{DigestCache, RedbCache, XattrCache};
+pub use checksum::{ChecksumAlgorithm, Digest, Hasher};
 pub use cli::Cli;
 pub use gui::Gui;
-pub use dupels::{DupeLs, DupeLsConfig};
+pub use dupels::{DupeLs, DupeLsConfig, EmptyFilePolicy, SymlinkPolicy};
+pub use error::DupeLsError;
+pub use file_type::FileType;
+pub use filter::PathFilter;
+pub use group::{DuplicateGroup, FileEntry};
+pub use output::OutputFormat;
+pub use snapshot::{ChangeKind, GroupChange, Snapshot, SnapshotGroup};
 
 pub const MAX_THREAD_LIMIT: usize = 32;
 pub const DEFAULT_DEPTH: usize = 2;
+pub const DEFAULT_SAMPLE_SIZE: usize = 4;
 const CHECKSUM_READ_BUFFER_SIZE: usize = 8192;
 
 impl From<&Cli> for DupeLsConfig {
@@ -17,13 +35,42 @@ impl From<&Cli> for DupeLsConfig {
             None => (cli.recursive, DEFAULT_DEPTH),
         };
         DupeLsConfig {
-            base_path: cli.file.clone(),
+            base_paths: cli.file.clone(),
             track_dot_files: cli.all,
             recursive,
             depth,
             seperator: cli.seperator.clone(),
             max_threads: cli.max_threads,
             omit: cli.omit,
+            hash: cli.hash,
+            sample_size: cli.sample_size,
+            verify: cli.paranoid,
+            format: cli.format,
+            null_terminated: cli.null,
+            escape: cli.escape,
+            count_hard_links: cli.count_hard_links,
+            symlinks: cli.symlinks,
+            filter: PathFilter::new(&cli.exclude, &cli.include),
+            respect_ignore: cli.respect_ignore,
+            types: cli.types.clone(),
+            min_size: cli.min_size.unwrap_or(0),
+            max_size: cli.max_size,
+            empty_files: cli.empty_files,
+            cross_root: cli.cross_root,
+            reference_paths: cli.reference.clone(),
+            one_file_system: cli.one_file_system,
+            walk_threads: cli.walk_threads,
+            cache_dir: if cli.cache || cli.purge_cache || cli.cache_dir.is_some() {
+                cli.cache_dir.clone().or_else(RedbCache::default_dir)
+            } else {
+                None
+            },
+            purge_cache: cli.purge_cache,
+            xattr_cache: cli.xattr_cache,
+            save_snapshot: cli.save.clone(),
+            since_snapshot: cli.since.clone(),
+            manifest: cli.emit_manifest.clone(),
+            from_manifests: cli.from_manifest.clone(),
         }
     }
 }
@@ -37,40 +84,44 @@ impl From<&Gui> for DupeLsConfig {
             d => (true, d),
         };
         DupeLsConfig {
-            base_path: Some(gui.directory.clone().into()),
+            base_paths: vec![gui.directory.clone().into()],
             track_dot_files: gui.all,
             recursive,
             depth,
             seperator: "===".to_string(),
             max_threads: None, // Let DupleLs resolve thread count.
             omit: gui.omit,
+            ..Default::default()
         }
     }
 }
 
-pub fn run_cli(args: &Cli) -> String{
+pub fn run_cli(args: &Cli) -> Vec<u8> {
     let config = DupeLsConfig::from(args);
     let mut dupels = DupeLs::new(config);
     dupels.parse();
-    dupels.get_output_string()
+    for err in dupels.get_errors() {
+        eprintln!("{}", err);
+    }
+    dupels.get_output_bytes()
 }
 
-pub fn run_gui(gui: &Gui) -> Vec<String> {
+pub fn run_gui(gui: &Gui) -> (Vec<DuplicateGroup>, Vec<DupeLsError>) {
     let config = DupeLsConfig::from(gui);
     let mut dupels = DupeLs::new(config);
     dupels.parse();
-    dupels.get_output_vec()
+    (dupels.get_groups(), dupels.take_errors())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cli::Cli;
-    use crate::gui::Gui;
+    use clap::Parser;
     use crate::dupels::DupeLsConfig;
-    use crate::run_cli;
+    use crate::{run_cli, ChecksumAlgorithm, RedbCache, DEFAULT_SAMPLE_SIZE, EmptyFilePolicy, FileType, OutputFormat, SymlinkPolicy};
     use std::fs::File;
     use std::ops::Not;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use std::io::Write;
     use tempfile::tempdir;
 
@@ -91,10 +142,11 @@ mod tests {
             omit: false,
             seperator: "===".to_string(),
             max_threads: Some(1),
-            file: Some(PathBuf::from("/tmp")),
+            file: vec![PathBuf::from("/tmp")],
+            ..Default::default()
         };
         let config = DupeLsConfig::from(&cli);
-        assert_eq!(config.base_path, Some(PathBuf::from("/tmp")));
+        assert_eq!(config.base_paths, vec![PathBuf::from("/tmp")]);
         assert!(config.track_dot_files);
         assert!(config.recursive);
         assert_eq!(config.depth, 5);
@@ -112,7 +164,8 @@ mod tests {
             omit: false,
             seperator: "===".to_string(),
             max_threads: Some(1),
-            file: Some(PathBuf::from("/tmp")),
+            file: vec![PathBuf::from("/tmp")],
+            ..Default::default()
         };
         let config = DupeLsConfig::from(&cli);
         assert!(config.recursive.not());
@@ -128,7 +181,8 @@ mod tests {
             omit: false,
             seperator: "===".to_string(),
             max_threads: Some(1),
-            file: Some(PathBuf::from("/tmp")),
+            file: vec![PathBuf::from("/tmp")],
+            ..Default::default()
         };
         let config = DupeLsConfig::from(&cli);
         assert!(config.recursive);
@@ -145,10 +199,215 @@ mod tests {
             omit: false,
             seperator: "===".to_string(),
             max_threads: Some(1),
-            file: Some(PathBuf::from(dir.path().to_path_buf())),
+            file: vec![dir.path().to_path_buf()],
+            ..Default::default()
         };
         let output = run_cli(&cli);
-        assert_eq!(output, file.to_string_lossy().to_string());
+        assert_eq!(output, file.to_string_lossy().as_bytes());
+    }
+
+    #[test]
+    fn test_cli_default_matches_parsed_defaults() {
+        let cli = Cli::default();
+        assert_eq!(cli.seperator, ">--");
+        assert_eq!(cli.sample_size, DEFAULT_SAMPLE_SIZE);
+        assert_eq!(cli.hash, ChecksumAlgorithm::default());
+        assert!(cli.file.is_empty());
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_hash() {
+        let cli = Cli {
+            hash: ChecksumAlgorithm::Blake3,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.hash, ChecksumAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_sample_size() {
+        let cli = Cli {
+            sample_size: 64,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.sample_size, 64);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_paranoid() {
+        let cli = Cli {
+            paranoid: true,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.verify);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_format() {
+        let cli = Cli {
+            format: OutputFormat::Ndjson,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.format, OutputFormat::Ndjson);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_null() {
+        let cli = Cli {
+            null: true,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.null_terminated);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_escape() {
+        let cli = Cli {
+            escape: true,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.escape);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_count_hard_links() {
+        let cli = Cli {
+            count_hard_links: true,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.count_hard_links);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_symlinks() {
+        let cli = Cli {
+            symlinks: SymlinkPolicy::Follow,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.symlinks, SymlinkPolicy::Follow);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_exclude_include() {
+        let cli = Cli::parse_from(["dupels", "--exclude", "target/", "--exclude", "*.log", "--include", "*.jpg"]);
+        assert_eq!(cli.exclude.len(), 2);
+        let config = DupeLsConfig::from(&cli);
+        assert!(!config.filter.walk_dir(Path::new("target")));
+        assert!(!config.filter.keep_file(Path::new("a.log")));
+        assert!(config.filter.keep_file(Path::new("a.jpg")));
+        assert!(!config.filter.keep_file(Path::new("a.png")));
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_respect_ignore() {
+        let cli = Cli {
+            respect_ignore: true,
+            ..Default::default()
+        };
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.respect_ignore);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_types() {
+        let cli = Cli::parse_from(["dupels", "--type", "audio,image", "--type", "archive"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.types, vec![FileType::Audio, FileType::Image, FileType::Archive]);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_size() {
+        let cli = Cli::parse_from(["dupels", "--min-size", "10K", "--max-size", "4M", "--empty-files", "list"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.min_size, 10 * 1024);
+        assert_eq!(config.max_size, Some(4 * 1024 * 1024));
+        assert_eq!(config.empty_files, EmptyFilePolicy::List);
+        assert_eq!(DupeLsConfig::from(&Cli::default()).min_size, 0);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_multiple_roots() {
+        let cli = Cli::parse_from(["dupels", "--cross-root", "/mnt/old_nas", "/mnt/new_nas"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.base_paths, vec![PathBuf::from("/mnt/old_nas"), PathBuf::from("/mnt/new_nas")]);
+        assert!(config.cross_root);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_reference() {
+        let cli = Cli::parse_from(["dupels", "--reference", "/library", "--reference", "/archive", "/uploads"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.base_paths, vec![PathBuf::from("/uploads")]);
+        assert_eq!(config.reference_paths, vec![PathBuf::from("/library"), PathBuf::from("/archive")]);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_one_file_system() {
+        let cli = Cli::parse_from(["dupels", "-x"]);
+        let config = DupeLsConfig::from(&cli);
+        assert!(config.one_file_system);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_walk_threads() {
+        let cli = Cli::parse_from(["dupels", "--max-threads", "2", "--walk-threads", "8"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.max_threads, Some(2));
+        assert_eq!(config.walk_threads, Some(8));
+        assert_eq!(config.resolved_walk_threads(), 8);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_cache() {
+        assert_eq!(DupeLsConfig::from(&Cli::default()).cache_dir, None);
+
+        let config = DupeLsConfig::from(&Cli::parse_from(["dupels", "--cache-dir", "/var/cache/dupels"]));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/var/cache/dupels")));
+        assert!(!config.purge_cache);
+
+        let config = DupeLsConfig::from(&Cli::parse_from(["dupels", "--purge-cache"]));
+        assert_eq!(config.cache_dir, RedbCache::default_dir());
+        assert!(config.purge_cache);
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_xattr_cache() {
+        let config = DupeLsConfig::from(&Cli::parse_from(["dupels", "--xattr-cache"]));
+        assert!(config.xattr_cache);
+        assert_eq!(config.cache_dir, None);
+        assert!(Cli::try_parse_from(["dupels", "--xattr-cache", "--cache"]).is_err());
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_snapshot() {
+        let cli = Cli::parse_from(["dupels", "--since", "weekly.json", "--save", "weekly.json"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.save_snapshot, Some(PathBuf::from("weekly.json")));
+        assert_eq!(config.since_snapshot, Some(PathBuf::from("weekly.json")));
+        assert!(Cli::try_parse_from(["dupels", "--since", "weekly.json", "-0"]).is_err());
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_emit_manifest() {
+        let cli = Cli::parse_from(["dupels", "--hash", "sha256", "--emit-manifest", "SHA256SUMS"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.manifest, Some(PathBuf::from("SHA256SUMS")));
+    }
+
+    #[test]
+    fn test_from_cli_for_dupe_ls_config_from_manifest() {
+        let cli = Cli::parse_from(["dupels", "--from-manifest", "tape1.md5", "--from-manifest", "tape2.md5", "/data"]);
+        let config = DupeLsConfig::from(&cli);
+        assert_eq!(config.from_manifests, vec![PathBuf::from("tape1.md5"), PathBuf::from("tape2.md5")]);
+        assert_eq!(config.base_paths, vec![PathBuf::from("/data")]);
     }
 
 }
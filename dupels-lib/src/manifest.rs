@@ -0,0 +1,182 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::output::{bytes_to_path, path_to_bytes};
+use crate::{ChecksumAlgorithm, Digest};
+
+/// Writes one `<hex digest>  <path>` line per file, the format of `md5sum` and `sha256sum`, sorted by path.
+/// A path that is listed more than once, e.g. as a hard link of another file, is written once.
+///
+/// Like coreutils, a path holding a backslash, newline or carriage return is escaped and its line starts
+/// with a backslash, so `md5sum -c` reads it back. Other bytes are written as is.
+pub(crate) fn write(path: &Path, files: &mut Vec<(&Path, &Digest)>) -> io::Result<()> {
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    files.dedup_by(|a, b| a.0 == b.0);
+    let mut manifest = Vec::new();
+    for (file, digest) in files.iter() {
+        let name = path_to_bytes(file);
+        let escape = name.iter().any(|byte| matches!(byte, b'\\' | b'\n' | b'\r'));
+        if escape {
+            manifest.push(b'\\');
+        }
+        manifest.extend_from_slice(format!("{:x}  ", digest).as_bytes());
+        for byte in name.iter() {
+            match byte {
+                b'\\' if escape => manifest.extend_from_slice(b"\\\\"),
+                b'\n' => manifest.extend_from_slice(b"\\n"),
+                b'\r' => manifest.extend_from_slice(b"\\r"),
+                byte => manifest.push(*byte),
+            }
+        }
+        manifest.push(b'\n');
+    }
+    fs::write(path, manifest)
+}
+
+/// Reads the files listed in a checksum manifest, in the format of `md5sum` and `sha256sum` or in the BSD format
+/// `MD5 (<path>) = <hex digest>` written by `md5sum --tag` and `md5 -r`. Empty lines and comments are skipped.
+///
+/// Returns the files and the number of lines that could not be read, either because they are improperly
+/// formatted or because they hold the checksum of another hash function than `algorithm`.
+pub(crate) fn read(path: &Path, algorithm: ChecksumAlgorithm) -> io::Result<(Vec<(PathBuf, Digest)>, usize)> {
+    let manifest = fs::read(path)?;
+    let digest_len = algorithm.hasher().finish().as_ref().len();
+    let mut files = Vec::new();
+    let mut improper = 0;
+    for line in manifest.split(|byte| *byte == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        match parse_line(line, algorithm, digest_len) {
+            Some(file) => files.push(file),
+            None => improper += 1,
+        }
+    }
+    Ok((files, improper))
+}
+
+fn parse_line(line: &[u8], algorithm: ChecksumAlgorithm, digest_len: usize) -> Option<(PathBuf, Digest)> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (name, hex) = match line.get(digest_len * 2..digest_len * 2 + 2) {
+        // GNU: `<hex digest>  <path>`, or `<hex digest> *<path>` for files read in binary mode.
+        Some(b"  " | b" *") => (&line[digest_len * 2 + 2..], &line[..digest_len * 2]),
+        // BSD: `<TAG> (<path>) = <hex digest>`.
+        _ => {
+            let tag_end = line.iter().position(|byte| *byte == b' ')?;
+            let (tag, rest) = line.split_at(tag_end);
+            if !tag.eq_ignore_ascii_case(algorithm.name().as_bytes()) {
+                return None;
+            }
+            let rest = rest.strip_prefix(b" (")?;
+            let separator = rest.windows(4).rposition(|window| window == b") = ")?;
+            (&rest[..separator], &rest[separator + 4..])
+        }
+    };
+    let digest = parse_hex(hex, digest_len)?;
+    let name = if escaped { unescape(name)? } else { name.to_vec() };
+    (!name.is_empty()).then(|| (bytes_to_path(name), digest))
+}
+
+fn parse_hex(hex: &[u8], digest_len: usize) -> Option<Digest> {
+    if hex.len() != digest_len * 2 {
+        return None;
+    }
+    let digest = hex
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(Digest::from(digest))
+}
+
+/// Reverses the escaping of `write`.
+fn unescape(name: &[u8]) -> Option<Vec<u8>> {
+    let mut unescaped = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte != b'\\' {
+            unescaped.push(*byte);
+            continue;
+        }
+        unescaped.push(match bytes.next()? {
+            b'\\' => b'\\',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write() {
+        let dir = tempdir().unwrap();
+        let manifest = dir.path().join("MD5SUMS");
+        let digest = Digest::from(vec![0xab, 0xcd]);
+        let mut files = vec![
+            (Path::new("b\nc.txt"), &digest),
+            (Path::new("a.txt"), &digest),
+            (Path::new("c\\d.txt"), &digest),
+            (Path::new("a.txt"), &digest),
+        ];
+        write(&manifest, &mut files).unwrap();
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), "abcd  a.txt\n\\abcd  b\\nc.txt\n\\abcd  c\\\\d.txt\n");
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempdir().unwrap();
+        let manifest = dir.path().join("MD5SUMS");
+        fs::write(
+            &manifest,
+            "# tape 42\n\
+             8b1a9953c4611296a827abf8c47804d7  /tape/hello.txt\n\
+             8b1a9953c4611296a827abf8c47804d7 */tape/binary mode.txt\r\n\
+             \\8b1a9953c4611296a827abf8c47804d7  /tape/new\\nline\\\\.txt\n\
+             MD5 (/tape/bsd (1).txt) = 8b1a9953c4611296a827abf8c47804d7\n\
+             \n\
+             SHA1 (/tape/sha1.txt) = f7ff9e8b7bb2e09b70935a5d785e0cc5d9d0abf0\n\
+             8b1a9953c4611296a827abf8c47804d  /tape/short.txt\n\
+             not a checksum\n",
+        )
+        .unwrap();
+        let (files, improper) = read(&manifest, ChecksumAlgorithm::Md5).unwrap();
+        let paths: Vec<&Path> = files.iter().map(|(path, _)| path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/tape/hello.txt"),
+                Path::new("/tape/binary mode.txt"),
+                Path::new("/tape/new\nline\\.txt"),
+                Path::new("/tape/bsd (1).txt"),
+            ]
+        );
+        assert!(files.iter().all(|(_, digest)| format!("{:x}", digest) == "8b1a9953c4611296a827abf8c47804d7"));
+        assert_eq!(improper, 3);
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let dir = tempdir().unwrap();
+        let manifest = dir.path().join("SHA256SUMS");
+        let digest = Digest::from(vec![7; 32]);
+        let mut files = vec![(Path::new("a\\b\nc\rd.txt"), &digest), (Path::new("plain.txt"), &digest)];
+        write(&manifest, &mut files).unwrap();
+        let (read, improper) = read(&manifest, ChecksumAlgorithm::Sha256).unwrap();
+        assert_eq!(improper, 0);
+        assert_eq!(
+            read,
+            vec![(PathBuf::from("a\\b\nc\rd.txt"), digest.clone()), (PathBuf::from("plain.txt"), digest)]
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{ChangeKind, DuplicateGroup, GroupChange};
+
+/// The format used to print the groups of files found by DupeLs.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// File paths, one per line, with groups divided by the seperator.
+    #[default]
+    Text,
+    /// A JSON array holding one object per group.
+    Json,
+    /// One JSON object per group, one group per line.
+    Ndjson,
+    /// One comma separated row per file: group_id, digest, size, path, mtime, root, hard_link_of and alias_of.
+    Csv,
+    /// One tab separated row per file: group_id, digest, size, path, mtime, root, hard_link_of and alias_of.
+    Tsv,
+}
+
+#[derive(Serialize)]
+struct JsonGroup<'a> {
+    digest: Option<String>,
+    size: u64,
+    files: Vec<Cow<'a, str>>,
+    roots: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hard_links: Vec<Vec<Cow<'a, str>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<Vec<Cow<'a, str>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    references: Vec<Cow<'a, str>>,
+}
+
+impl<'a> JsonGroup<'a> {
+    fn new(group: &'a DuplicateGroup, escape: bool) -> Self {
+        JsonGroup {
+            digest: group.digest.as_ref().map(|digest| format!("{:x}", digest)),
+            size: group.size,
+            files: group.files.iter().map(|file| path_to_string(&file.path, escape)).collect(),
+            // The root of each file, in the order of `files`.
+            roots: group.files.iter().map(|file| file.root).collect(),
+            // Each set of links is listed once, under the link with the lowest path.
+            hard_links: group
+                .files
+                .iter()
+                .filter(|file| !file.hard_links.is_empty() && file.hard_links.iter().all(|link| file.path < *link))
+                .map(|file| {
+                    std::iter::once(&file.path)
+                        .chain(&file.hard_links)
+                        .map(|path| path_to_string(path, escape))
+                        .collect()
+                })
+                .collect(),
+            aliases: group
+                .files
+                .iter()
+                .filter(|file| !file.aliases.is_empty())
+                .map(|file| {
+                    std::iter::once(&file.path)
+                        .chain(&file.aliases)
+                        .map(|path| path_to_string(path, escape))
+                        .collect()
+                })
+                .collect(),
+            references: group.references().map(|file| path_to_string(&file.path, escape)).collect(),
+        }
+    }
+}
+
+/// Converts a path to a string for display.
+///
+/// Bytes that are not valid UTF-8 are always written as `\xNN`, and the backslashes of such a path are
+/// written as `\\` so a literal `\xNN` in a name can't be confused with an invalid byte. If `escape` is set,
+/// every path is escaped like this and control characters such as newlines are escaped as well, so every
+/// path fits on one line.
+pub(crate) fn path_to_string(path: &Path, escape: bool) -> Cow<'_, str> {
+    if !escape && let Some(path) = path.to_str() {
+        return Cow::Borrowed(path);
+    }
+    let mut escaped = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                c if escape && c.is_control() => escaped.extend(c.escape_default()),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "\\x{:02x}", byte);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// The raw bytes of a path, written as is by the text output. Platforms that don't store paths as
+/// bytes fall back to `path_to_string`.
+pub(crate) fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        match path_to_string(path, false) {
+            Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+            Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+        }
+    }
+}
+
+/// Turns the raw bytes of a path back into a path. Platforms that don't store paths as bytes need UTF-8.
+pub(crate) fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(std::ffi::OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+pub(crate) fn to_json(groups: &[DuplicateGroup], escape: bool) -> String {
+    let groups: Vec<JsonGroup> = groups.iter().map(|group| JsonGroup::new(group, escape)).collect();
+    serde_json::to_string_pretty(&groups).expect("Could not serialize groups")
+}
+
+pub(crate) fn to_ndjson(groups: &[DuplicateGroup], escape: bool) -> String {
+    groups
+        .iter()
+        .map(|group| serde_json::to_string(&JsonGroup::new(group, escape)).expect("Could not serialize group"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes one row per file. `mtime` is in seconds since the Unix epoch, `digest` and `mtime` are left
+/// empty when unknown. `root` is the index of the root directory the file was found in. Hard links that aren't
+/// listed on their own get a row after their file, with the path of the file in `hard_link_of` and no root. So do
+/// the symlinks to the file, with its path in `alias_of`.
+pub(crate) fn to_csv(groups: &[DuplicateGroup], delimiter: u8, escape: bool) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    writer
+        .write_record(["group_id", "digest", "size", "path", "mtime", "root", "hard_link_of", "alias_of"])
+        .expect("Could not write header");
+    for (i, group) in groups.iter().enumerate() {
+        let digest = group.digest.as_ref().map(|digest| format!("{:x}", digest)).unwrap_or_default();
+        for file in &group.files {
+            let mtime = file
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs().to_string())
+                .unwrap_or_default();
+            let group_id = (i + 1).to_string();
+            let size = file.size.to_string();
+            let path = path_to_string(&file.path, escape);
+            writer
+                .write_record([&group_id, &digest, &size, path.as_ref(), &mtime, &file.root.to_string(), "", ""])
+                .expect("Could not write row");
+            for link in group.unlisted_hard_links(file) {
+                let link = path_to_string(link, escape);
+                writer
+                    .write_record([&group_id, &digest, &size, link.as_ref(), &mtime, "", path.as_ref(), ""])
+                    .expect("Could not write row");
+            }
+            for alias in &file.aliases {
+                let alias = path_to_string(alias, escape);
+                writer
+                    .write_record([&group_id, &digest, &size, alias.as_ref(), &mtime, "", "", path.as_ref()])
+                    .expect("Could not write row");
+            }
+        }
+    }
+    let bytes = writer.into_inner().expect("Could not flush rows");
+    String::from_utf8(bytes).expect("Rows are valid UTF-8").trim_end_matches('\n').to_string()
+}
+
+#[derive(Serialize)]
+struct JsonChange<'a> {
+    change: ChangeKind,
+    digest: &'a str,
+    size: u64,
+    added: Vec<Cow<'a, str>>,
+    removed: Vec<Cow<'a, str>>,
+    kept: Vec<Cow<'a, str>>,
+}
+
+impl<'a> JsonChange<'a> {
+    fn new(change: &'a GroupChange, escape: bool) -> Self {
+        let paths = |paths: &'a [PathBuf]| paths.iter().map(|path| path_to_string(path, escape)).collect();
+        JsonChange {
+            change: change.change,
+            digest: &change.digest,
+            size: change.size,
+            added: paths(&change.added),
+            removed: paths(&change.removed),
+            kept: paths(&change.kept),
+        }
+    }
+}
+
+/// The files of a changed group, each with its status: `added`, `removed` or `kept`.
+fn change_files(change: &GroupChange) -> impl Iterator<Item = (&'static str, &PathBuf)> {
+    let kept = change.kept.iter().map(|path| ("kept", path));
+    let added = change.added.iter().map(|path| ("added", path));
+    let removed = change.removed.iter().map(|path| ("removed", path));
+    kept.chain(added).chain(removed)
+}
+
+/// Prints every changed group as a line with the change, digest and size, followed by its files. Added files
+/// are prefixed with `+ `, removed files with `- ` and kept files with two spaces.
+pub(crate) fn changes_to_text(changes: &[GroupChange], seperator: &str, escape: bool) -> String {
+    let text = write_changes(changes, seperator, |path| match path_to_string(path, escape) {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+    });
+    String::from_utf8(text).expect("Paths are converted to strings")
+}
+
+/// Like `changes_to_text`, but writes every path as it is stored by the OS.
+pub(crate) fn changes_to_raw_text(changes: &[GroupChange], seperator: &str) -> Vec<u8> {
+    write_changes(changes, seperator, |path| path_to_bytes(path))
+}
+
+fn write_changes<'a>(
+    changes: &'a [GroupChange],
+    seperator: &str,
+    path_bytes: impl Fn(&'a Path) -> Cow<'a, [u8]>,
+) -> Vec<u8> {
+    let mut output = Vec::new();
+    for (i, change) in changes.iter().enumerate() {
+        if i > 0 {
+            output.extend_from_slice(seperator.as_bytes());
+            output.push(b'\n');
+        }
+        output.extend_from_slice(format!("{} {} {}\n", change.change, change.digest, change.size).as_bytes());
+        for (status, path) in change_files(change) {
+            output.extend_from_slice(match status {
+                "added" => b"+ ",
+                "removed" => b"- ",
+                _ => b"  ",
+            });
+            output.extend_from_slice(&path_bytes(path));
+            output.push(b'\n');
+        }
+    }
+    output.pop(); // Like the groups, the output doesn't end with a newline.
+    output
+}
+
+pub(crate) fn changes_to_json(changes: &[GroupChange], escape: bool) -> String {
+    let changes: Vec<JsonChange> = changes.iter().map(|change| JsonChange::new(change, escape)).collect();
+    serde_json::to_string_pretty(&changes).expect("Could not serialize changes")
+}
+
+pub(crate) fn changes_to_ndjson(changes: &[GroupChange], escape: bool) -> String {
+    changes
+        .iter()
+        .map(|change| serde_json::to_string(&JsonChange::new(change, escape)).expect("Could not serialize change"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes one row per file of every changed group: change, digest, size, status and path.
+pub(crate) fn changes_to_csv(changes: &[GroupChange], delimiter: u8, escape: bool) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    writer
+        .write_record(["change", "digest", "size", "status", "path"])
+        .expect("Could not write header");
+    for change in changes {
+        for (status, path) in change_files(change) {
+            let path = path_to_string(path, escape);
+            writer
+                .write_record([change.change.name(), &change.digest, &change.size.to_string(), status, path.as_ref()])
+                .expect("Could not write row");
+        }
+    }
+    let bytes = writer.into_inner().expect("Could not flush rows");
+    String::from_utf8(bytes).expect("Rows are valid UTF-8").trim_end_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FileEntry;
+
+    fn test_groups() -> Vec<DuplicateGroup> {
+        let file = |path: &str, size: u64| FileEntry {
+            path: path.into(),
+            size,
+            modified: None,
+            hard_links: Vec::new(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        vec![
+            DuplicateGroup::new(None, vec![file("a\nb.txt", 3)]),
+            DuplicateGroup::new(None, vec![file(">--", 7), FileEntry { root: 1, ..file("c.txt", 7) }]),
+        ]
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&test_groups(), false)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "digest": null, "size": 3, "files": ["a\nb.txt"], "roots": [0] },
+                { "digest": null, "size": 7, "files": [">--", "c.txt"], "roots": [0, 1] },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_to_ndjson() {
+        let ndjson = to_ndjson(&test_groups(), false);
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], r#"{"digest":null,"size":3,"files":["a\nb.txt"],"roots":[0]}"#);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let csv = to_csv(&test_groups(), b',', false);
+        assert_eq!(
+            csv,
+            "group_id,digest,size,path,mtime,root,hard_link_of,alias_of\n1,,3,\"a\nb.txt\",,0,,\n2,,7,>--,,0,,\n2,,7,c.txt,,1,,"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_hard_links() {
+        let file = FileEntry {
+            path: "a".into(),
+            size: 5,
+            modified: None,
+            hard_links: vec!["b".into(), "c".into()],
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        let listed = FileEntry { path: "c".into(), hard_links: vec!["a".into(), "b".into()], ..file.clone() };
+        let csv = to_csv(&[DuplicateGroup::new(None, vec![file, listed])], b',', false);
+        assert_eq!(
+            csv,
+            "group_id,digest,size,path,mtime,root,hard_link_of,alias_of\n1,,5,a,,0,,\n1,,5,b,,,a,\n1,,5,c,,0,,\n1,,5,b,,,c,"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_aliases() {
+        let file = FileEntry {
+            path: "a".into(),
+            size: 5,
+            modified: None,
+            hard_links: Vec::new(),
+            aliases: vec!["link1".into(), "link2".into()],
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        let csv = to_csv(&[DuplicateGroup::new(None, vec![file])], b',', false);
+        assert_eq!(csv, "group_id,digest,size,path,mtime,root,hard_link_of,alias_of\n1,,5,a,,0,,\n1,,5,link1,,,,a\n1,,5,link2,,,,a");
+    }
+
+    #[test]
+    fn test_to_tsv_quotes_tabs() {
+        let file = FileEntry {
+            path: "tab\there.txt".into(),
+            size: 1,
+            modified: Some(UNIX_EPOCH + std::time::Duration::from_secs(60)),
+            hard_links: Vec::new(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        let tsv = to_csv(&[DuplicateGroup::new(None, vec![file])], b'\t', false);
+        assert_eq!(tsv, "group_id\tdigest\tsize\tpath\tmtime\troot\thard_link_of\talias_of\n1\t\t1\t\"tab\there.txt\"\t60\t0\t\t");
+    }
+
+    #[test]
+    fn test_empty_output() {
+        assert_eq!(to_json(&[], false), "[]");
+        assert_eq!(to_ndjson(&[], false), "");
+        assert_eq!(to_csv(&[], b',', false), "group_id,digest,size,path,mtime,root,hard_link_of,alias_of");
+    }
+
+    #[test]
+    fn test_path_to_string_escape() {
+        let path = Path::new("a\\b\nc.txt");
+        assert_eq!(path_to_string(path, false), "a\\b\nc.txt");
+        assert_eq!(path_to_string(path, true), "a\\\\b\\nc.txt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_to_string_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        assert_eq!(path_to_string(path, false), "caf\\xe9.txt");
+        assert_eq!(path_to_string(path, true), "caf\\xe9.txt");
+        assert_eq!(path_to_bytes(path).as_ref(), b"caf\xe9.txt");
+
+        // A name holding a literal `\xe9` isn't mistaken for the invalid byte.
+        let literal = Path::new(std::ffi::OsStr::from_bytes(b"caf\\xe9.txt\xff"));
+        assert_eq!(path_to_string(literal, false), "caf\\\\xe9.txt\\xff");
+
+        let file = FileEntry {
+            path: path.into(),
+            size: 1,
+            modified: None,
+            hard_links: Vec::new(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        let group = DuplicateGroup::new(None, vec![file]);
+        assert_eq!(to_ndjson(&[group], false), r#"{"digest":null,"size":1,"files":["caf\\xe9.txt"],"roots":[0]}"#);
+    }
+
+    #[test]
+    fn test_to_ndjson_hard_links() {
+        let file = |path: &str, hard_links: &[&str]| FileEntry {
+            path: path.into(),
+            size: 5,
+            modified: None,
+            hard_links: hard_links.iter().map(|link| link.into()).collect(),
+            aliases: Vec::new(),
+            symlink: false,
+            root: 0,
+            reference: false,
+            file_id: None,
+        };
+        let group = DuplicateGroup::new(None, vec![file("b", &["a"]), file("a", &["b"]), file("c", &[])]);
+        assert_eq!(
+            to_ndjson(&[group], false),
+            r#"{"digest":null,"size":5,"files":["a","b","c"],"roots":[0,0,0],"hard_links":[["a","b"]]}"#
+        );
+    }
+
+    fn test_changes() -> Vec<GroupChange> {
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect();
+        vec![
+            GroupChange {
+                change: ChangeKind::New,
+                digest: "aa".to_string(),
+                size: 5,
+                added: paths(&["a", "b"]),
+                removed: Vec::new(),
+                kept: Vec::new(),
+            },
+            GroupChange {
+                change: ChangeKind::Changed,
+                digest: "bb".to_string(),
+                size: 7,
+                added: paths(&["d"]),
+                removed: paths(&["e"]),
+                kept: paths(&["c"]),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_changes_to_text() {
+        assert_eq!(
+            changes_to_text(&test_changes(), ">--", false),
+            "new aa 5\n+ a\n+ b\n>--\nchanged bb 7\n  c\n+ d\n- e"
+        );
+        assert_eq!(changes_to_raw_text(&test_changes(), ">--"), changes_to_text(&test_changes(), ">--", false).into_bytes());
+        assert_eq!(changes_to_text(&[], ">--", false), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_changes_escape() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"a\nb\xe9"));
+        let change = GroupChange {
+            change: ChangeKind::New,
+            digest: "aa".to_string(),
+            size: 5,
+            added: vec![path.to_path_buf()],
+            removed: Vec::new(),
+            kept: Vec::new(),
+        };
+        let changes = [change];
+        assert_eq!(changes_to_raw_text(&changes, ">--"), b"new aa 5\n+ a\nb\xe9");
+        assert_eq!(changes_to_text(&changes, ">--", false), "new aa 5\n+ a\nb\\xe9");
+        assert_eq!(changes_to_text(&changes, ">--", true), "new aa 5\n+ a\\nb\\xe9");
+        assert_eq!(changes_to_csv(&changes, b',', true), "change,digest,size,status,path\nnew,aa,5,added,a\\nb\\xe9");
+        assert_eq!(
+            changes_to_ndjson(&changes, true),
+            r#"{"change":"new","digest":"aa","size":5,"added":["a\\nb\\xe9"],"removed":[],"kept":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_changes_to_csv() {
+        assert_eq!(
+            changes_to_csv(&test_changes(), b',', false),
+            "change,digest,size,status,path\nnew,aa,5,added,a\nnew,aa,5,added,b\n\
+             changed,bb,7,kept,c\nchanged,bb,7,added,d\nchanged,bb,7,removed,e"
+        );
+        assert_eq!(
+            changes_to_ndjson(&test_changes()[..1], false),
+            r#"{"change":"new","digest":"aa","size":5,"added":["a","b"],"removed":[],"kept":[]}"#
+        );
+    }
+}
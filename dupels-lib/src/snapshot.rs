@@ -0,0 +1,268 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::output::{bytes_to_path, path_to_bytes};
+use crate::{ChecksumAlgorithm, DuplicateGroup};
+
+/// The duplicate groups found by a scan, saved with `--save` and compared against with `--since`.
+///
+/// # Fields
+/// - `hash`: The name of the hash function that computed the digests.
+/// - `groups`: The groups of files with a digest. Unique files are only recorded when they were hashed, e.g.
+///   because they were in a group of the previous snapshot.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub hash: String,
+    pub groups: Vec<SnapshotGroup>,
+}
+
+/// A group of files in a `Snapshot`. Paths are saved as the hex of their raw bytes, so any path is valid JSON
+/// and is read back unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotGroup {
+    pub digest: String,
+    pub size: u64,
+    #[serde(serialize_with = "serialize_paths", deserialize_with = "deserialize_paths")]
+    pub files: Vec<PathBuf>,
+}
+
+/// How a group of duplicates changed since a snapshot.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// The files weren't duplicates in the snapshot.
+    New,
+    /// The group gained or lost files.
+    Changed,
+    /// The group has less than two files left.
+    Resolved,
+}
+
+/// A group of duplicates that changed since a snapshot. Groups are matched by their digest.
+///
+/// # Fields
+/// - `change`: How the group changed.
+/// - `digest`, `size`: The digest and size shared by the files.
+/// - `added`: The files that joined the group since the snapshot.
+/// - `removed`: The files that left the group, they were deleted or their contents changed.
+/// - `kept`: The files that still have the digest of the group, even if they are unique now.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GroupChange {
+    pub change: ChangeKind,
+    pub digest: String,
+    pub size: u64,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub kept: Vec<PathBuf>,
+}
+
+impl ChangeKind {
+    /// The lowercase name of the change, as written in every output format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::New => "new",
+            ChangeKind::Changed => "changed",
+            ChangeKind::Resolved => "resolved",
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Snapshot {
+    /// Records the groups with a digest, files that were never hashed are left out.
+    pub fn new(groups: &[DuplicateGroup], hash: ChecksumAlgorithm) -> Snapshot {
+        let groups = groups
+            .iter()
+            .filter_map(|group| {
+                Some(SnapshotGroup {
+                    digest: format!("{:x}", group.digest.as_ref()?),
+                    size: group.size,
+                    files: group.files.iter().map(|file| file.path.clone()).collect(),
+                })
+            })
+            .collect();
+        Snapshot { hash: hash.name().to_string(), groups }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Snapshot> {
+        let snapshot = fs::read(path)?;
+        serde_json::from_slice(&snapshot).map_err(io::Error::from)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// Lists the groups that are new, changed or resolved compared to `older`, sorted by change and digest. A
+    /// group is a duplicate while it holds more than one file.
+    pub fn changes_since(&self, older: &Snapshot) -> Vec<GroupChange> {
+        let newer = self.by_digest();
+        let older = older.by_digest();
+        let digests: BTreeSet<&str> = newer.keys().chain(older.keys()).copied().collect();
+        let mut changes = Vec::new();
+        for digest in digests {
+            let empty = (0, BTreeSet::new());
+            let (size, new_files) = newer.get(digest).unwrap_or(&empty);
+            let (old_size, old_files) = older.get(digest).unwrap_or(&empty);
+            let change = match (new_files.len() > 1, old_files.len() > 1) {
+                (true, false) => ChangeKind::New,
+                (false, true) => ChangeKind::Resolved,
+                (true, true) if new_files != old_files => ChangeKind::Changed,
+                _ => continue,
+            };
+            changes.push(GroupChange {
+                change,
+                digest: digest.to_string(),
+                size: *size.max(old_size),
+                added: new_files.difference(old_files).map(|path| path.to_path_buf()).collect(),
+                removed: old_files.difference(new_files).map(|path| path.to_path_buf()).collect(),
+                kept: new_files.intersection(old_files).map(|path| path.to_path_buf()).collect(),
+            });
+        }
+        // The digests are already sorted.
+        changes.sort_by_key(|change| change.change);
+        changes
+    }
+
+    /// The files of every group by digest. Groups that share a digest after a verified collision are merged.
+    fn by_digest(&self) -> BTreeMap<&str, (u64, BTreeSet<&Path>)> {
+        let mut groups: BTreeMap<&str, (u64, BTreeSet<&Path>)> = BTreeMap::new();
+        for group in &self.groups {
+            let (size, files) = groups.entry(&group.digest).or_default();
+            *size = group.size;
+            files.extend(group.files.iter().map(PathBuf::as_path));
+        }
+        groups
+    }
+
+    /// Every path recorded in the snapshot.
+    pub(crate) fn paths(&self) -> BTreeSet<&Path> {
+        self.groups.iter().flat_map(|group| &group.files).map(PathBuf::as_path).collect()
+    }
+}
+
+fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| {
+        path_to_bytes(path).iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+    }))
+}
+
+fn deserialize_paths<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    let paths: Vec<String> = Vec::deserialize(deserializer)?;
+    paths
+        .iter()
+        .map(|hex| {
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| {
+                    let byte = hex.get(i..i + 2).filter(|byte| byte.bytes().all(|c| c.is_ascii_hexdigit()))?;
+                    u8::from_str_radix(byte, 16).ok()
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| serde::de::Error::custom(format!("'{}' is not a hex encoded path", hex)))?;
+            Ok(bytes_to_path(bytes))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn snapshot(groups: &[(&str, &[&str])]) -> Snapshot {
+        Snapshot {
+            hash: "md5".to_string(),
+            groups: groups
+                .iter()
+                .map(|(digest, files)| SnapshotGroup {
+                    digest: digest.to_string(),
+                    size: 5,
+                    files: files.iter().map(PathBuf::from).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let older = snapshot(&[("aa", &["/a", "/b"]), ("bb", &["/c", "/d"]), ("cc", &["/e", "/f"]), ("ee", &["/j"])]);
+        let newer = snapshot(&[("aa", &["/a", "/b"]), ("bb", &["/c", "/g"]), ("cc", &["/e"]), ("dd", &["/h", "/i"])]);
+        let changes = newer.changes_since(&older);
+        assert_eq!(
+            changes,
+            vec![
+                GroupChange {
+                    change: ChangeKind::New,
+                    digest: "dd".to_string(),
+                    size: 5,
+                    added: paths(&["/h", "/i"]),
+                    removed: vec![],
+                    kept: vec![],
+                },
+                GroupChange {
+                    change: ChangeKind::Changed,
+                    digest: "bb".to_string(),
+                    size: 5,
+                    added: paths(&["/g"]),
+                    removed: paths(&["/d"]),
+                    kept: paths(&["/c"]),
+                },
+                // The file left of a resolved group is kept, only the file that is gone is removed.
+                GroupChange {
+                    change: ChangeKind::Resolved,
+                    digest: "cc".to_string(),
+                    size: 5,
+                    added: vec![],
+                    removed: paths(&["/f"]),
+                    kept: paths(&["/e"]),
+                },
+            ]
+        );
+        assert!(newer.changes_since(&newer).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        let saved = snapshot(&[("aa", &["/a", "/b\\n"])]);
+        saved.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), saved);
+        assert!(fs::read_to_string(&path).unwrap().contains(r#""files":["2f61","2f625c6e"]"#));
+
+        fs::write(&path, r#"{"hash":"md5","groups":[{"digest":"aa","size":5,"files":["2f6"]}]}"#).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_and_load_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        let mut saved = snapshot(&[("aa", &[])]);
+        saved.groups[0].files.push(Path::new(std::ffi::OsStr::from_bytes(b"/caf\xe9\\xe9")).to_path_buf());
+        saved.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), saved);
+    }
+}